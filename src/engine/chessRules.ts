@@ -22,6 +22,79 @@ export interface Position {
   rank: number; // 0-7
 }
 
+/**
+ * The same square seen from the opposite side of the board (e4 <-> d5).
+ * Rotating twice returns the original square.
+ */
+export function rotatePosition(position: Position): Position {
+  return { file: 7 - position.file, rank: 7 - position.rank };
+}
+
+/**
+ * The square `fileDelta` files and `rankDelta` ranks away from `position`,
+ * or null if that is off the board.
+ */
+export function offsetPosition(
+  position: Position,
+  fileDelta: number,
+  rankDelta: number
+): Position | null {
+  const file = position.file + fileDelta;
+  const rank = position.rank + rankDelta;
+  if (file < 0 || file > 7 || rank < 0 || rank > 7) return null;
+  return { file, rank };
+}
+
+/**
+ * Squares from `position` (exclusive) to the board edge, stepping by
+ * (`fileDelta`, `rankDelta`) — pure geometry, ignoring any pieces. Empty for
+ * a zero step.
+ */
+export function rayFrom(
+  position: Position,
+  fileDelta: number,
+  rankDelta: number
+): Position[] {
+  const ray: Position[] = [];
+  if (fileDelta === 0 && rankDelta === 0) return ray;
+  let next = offsetPosition(position, fileDelta, rankDelta);
+  while (next) {
+    ray.push(next);
+    next = offsetPosition(next, fileDelta, rankDelta);
+  }
+  return ray;
+}
+
+/**
+ * The eight rays from `position` to the board edge, as drawn for line
+ * guides: north (towards rank 8), north-east, east, south-east, south,
+ * south-west, west and north-west, in that order.
+ */
+export function raysFrom(position: Position): Position[][] {
+  const directions: Array<[number, number]> = [
+    [0, 1],
+    [1, 1],
+    [1, 0],
+    [1, -1],
+    [0, -1],
+    [-1, -1],
+    [-1, 0],
+    [-1, 1],
+  ];
+  return directions.map(([df, dr]) => rayFrom(position, df, dr));
+}
+
+/** Square index 0-63: rank * 8 + file, so a1 = 0, h1 = 7 and h8 = 63. */
+export function positionToIndex(position: Position): number {
+  return position.rank * 8 + position.file;
+}
+
+/** Inverse of positionToIndex(); null unless `index` is an integer 0-63. */
+export function positionFromIndex(index: number): Position | null {
+  if (!Number.isInteger(index) || index < 0 || index > 63) return null;
+  return { file: index % 8, rank: Math.floor(index / 8) };
+}
+
 export interface Move {
   fromFile: number;
   fromRank: number;
@@ -76,6 +149,47 @@ export interface GameResult {
     | 'insufficient_material';
 }
 
+/** Named checkmate patterns recognised by ChessRules.getMatePattern(). */
+export type MatePattern = 'back_rank' | 'smothered' | 'anastasia';
+
+/** Operations attached to an EPD record, returned by setPositionFromEPD(). */
+export interface EPDOperations {
+  bestMoves: string[]; // "bm" operands, in SAN
+  id: string | null; // "id" operand with quotes removed
+  all: Record<string, string[]>; // Every opcode with its raw operands
+}
+
+/** A piece pinned to its king, as reported by getPinnedPieces(). */
+export interface PinnedPiece {
+  position: Position;
+  pinnedBy: Position;
+  ray: Position[]; // Squares it may still move to: up to and incl. the pinner
+}
+
+/** What kind of move a Move is, as reported by classifyMove(). */
+export interface MoveClassification {
+  isCapture: boolean; // Includes en passant
+  isCastle: boolean;
+  isEnPassant: boolean;
+  isPromotion: boolean;
+  isDoublePush: boolean;
+}
+
+/**
+ * How a move checks the enemy king, as reported by getCheckType(): by the
+ * moved piece, by a piece it uncovered, or by both at once.
+ */
+export type CheckType = 'none' | 'direct' | 'discovered' | 'double';
+
+/**
+ * The pieces of each side attacking a square, as reported by
+ * getExchangeParticipants(), each list cheapest first.
+ */
+export interface ExchangeParticipants {
+  white: Array<{ position: Position; piece: Piece }>;
+  black: Array<{ position: Position; piece: Piece }>;
+}
+
 export type MoveType =
   'normal' | 'capture' | 'castling' | 'enPassant' | 'promotion';
 
@@ -89,8 +203,17 @@ export interface HistoryEntry {
   isCheck: boolean;
   isCheckmate: boolean;
   algebraic: string;
+  comment?: string; // Annotation emitted by toPGN() as {comment}
+  nags?: number[]; // Numeric annotation glyphs, emitted as $1, $2, ...
 }
 
+/**
+ * Called by ChessRules after every move made with makeMove() (undone =
+ * false) and every move taken back with undoMove() (undone = true). `fen`
+ * is the position after the move, or after taking it back.
+ */
+export type MoveCallback = (move: Move, fen: string, undone: boolean) => void;
+
 export interface ChessError {
   type: 'invalid_move' | 'callback_error' | 'timeout';
   player: Color;
@@ -99,44 +222,97 @@ export interface ChessError {
   originalError?: Error;
 }
 
+const STARTING_FEN =
+  'rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1';
+
+interface CastlingRights {
+  whiteKingSide: boolean;
+  whiteQueenSide: boolean;
+  blackKingSide: boolean;
+  blackQueenSide: boolean;
+}
+
+/** Engine state described by a FEN, produced by parseFEN(). */
+interface ParsedFEN {
+  board: (Piece | null)[][];
+  currentPlayer: Color;
+  castlingRights: CastlingRights;
+  enPassantTarget: Position | null;
+  halfmoveClock: number;
+  fullmoveNumber: number;
+}
+
+/** Engine state captured before a move (or null move) so it can be undone. */
+interface UndoSnapshot {
+  board: (Piece | null)[][];
+  currentPlayer: Color;
+  enPassantTarget: Position | null;
+  lastMove: Move | null;
+  castlingRights: CastlingRights;
+  halfmoveClock: number;
+  fullmoveNumber: number;
+  historyLength: number;
+  squareChangedAt: number[][];
+}
+
 export class ChessRules {
-  private board: (Piece | null)[][];
-  private currentPlayer: Color;
-  private enPassantTarget: Position | null; // Square where en passant capture can happen
-  private lastMove: Move | null; // Track the last move made
-  private moveHistory: Move[]; // Track all moves made
-  private historyEntries: HistoryEntry[]; // Rich history with piece/capture/notation info
-  private fenHistory: string[]; // FEN at each position (index 0 = initial, index n = after move n)
-  private halfmoveClock: number; // Moves since last capture or pawn move (for 50-move rule)
-  private fullmoveNumber: number; // Increments after Black's move
-  private castlingRights: {
-    whiteKingSide: boolean;
-    whiteQueenSide: boolean;
-    blackKingSide: boolean;
-    blackQueenSide: boolean;
-  };
+  // Game state below is assigned by resetGame(), which the constructor calls.
+  private board!: (Piece | null)[][];
+  private currentPlayer!: Color;
+  private enPassantTarget!: Position | null; // Square where en passant capture can happen
+  private lastMove!: Move | null; // Track the last move made
+  private moveHistory!: Move[]; // Track all moves made
+  private historyEntries!: HistoryEntry[]; // Rich history with piece/capture/notation info
+  private fenHistory!: string[]; // FEN at each position (index 0 = initial, index n = after move n)
+  private halfmoveClock!: number; // Moves since last capture or pawn move (for 50-move rule)
+  private fullmoveNumber!: number; // Increments after Black's move
+  private castlingRights!: CastlingRights;
+  private squareChangedAt!: number[][]; // Ply of each square's last change
+  private undoStack: UndoSnapshot[] = []; // One entry per move or null move
+  private redoEntries: HistoryEntry[] = []; // Moves ahead of gotoPly()'s ply
+  private resignedBy: Color | null = null;
+  private onMove: MoveCallback | null = null; // See setOnMove()
+  private freePlay = false; // See setFreePlay()
   // getGameState() is expensive (full legal move enumeration) and callers
   // invoke it several times per position — cache until the position mutates.
   private cachedGameState: GameState | null = null;
+  // Centipawn weights used by material counting. The king is never traded,
+  // so it carries no material value.
+  private pieceValues: Record<PieceType, number> = {
+    ...ChessRules.DEFAULT_PIECE_VALUES,
+  };
+
+  private static readonly DEFAULT_PIECE_VALUES: Readonly<
+    Record<PieceType, number>
+  > = {
+    [PieceType.Pawn]: 100,
+    [PieceType.Knight]: 320,
+    [PieceType.Bishop]: 330,
+    [PieceType.Rook]: 500,
+    [PieceType.Queen]: 900,
+    [PieceType.King]: 0,
+  };
 
   constructor() {
-    this.board = this.createEmptyBoard();
-    this.currentPlayer = Color.White;
-    this.enPassantTarget = null;
-    this.lastMove = null;
-    this.moveHistory = [];
-    this.historyEntries = [];
-    this.fenHistory = [];
-    this.halfmoveClock = 0;
-    this.fullmoveNumber = 1;
-    this.castlingRights = {
-      whiteKingSide: true,
-      whiteQueenSide: true,
-      blackKingSide: true,
-      blackQueenSide: true,
+    this.resetGame();
+  }
+
+  /**
+   * An engine with an empty board: no pieces, White to move, no castling
+   * rights and no en passant square — the blank starting point for a
+   * position editor. Its FEN is "8/8/8/8/8/8/8/8 w - - 0 1".
+   */
+  public static empty(): ChessRules {
+    const engine = new ChessRules();
+    engine.board = engine.createEmptyBoard();
+    engine.castlingRights = {
+      whiteKingSide: false,
+      whiteQueenSide: false,
+      blackKingSide: false,
+      blackQueenSide: false,
     };
-    this.setupInitialPosition();
-    this.fenHistory.push(this.generateFEN());
+    engine.fenHistory = [engine.generateFEN()];
+    return engine;
   }
 
   private createEmptyBoard(): (Piece | null)[][] {
@@ -190,6 +366,192 @@ export class ChessRules {
     return this.board[rank][file];
   }
 
+  /**
+   * Every occupied square with its piece, ordered by rank then file
+   * (a1, b1, ... h8). Empty squares are omitted.
+   */
+  public getPieces(): Array<{ position: Position; piece: Piece }> {
+    const pieces: Array<{ position: Position; piece: Piece }> = [];
+    for (let rank = 0; rank < 8; rank++) {
+      for (let file = 0; file < 8; file++) {
+        const piece = this.board[rank][file];
+        if (piece) {
+          pieces.push({ position: { file, rank }, piece: { ...piece } });
+        }
+      }
+    }
+    return pieces;
+  }
+
+  /**
+   * The board as 64 bytes for canvas/WebGL renderers, byte `rank * 8 + file`
+   * per square (byte 0 = a1, byte 63 = h8). 0 is an empty square; otherwise
+   * the byte is PieceType + 1 for White (1 = pawn, 2 = rook, 3 = knight,
+   * 4 = bishop, 5 = queen, 6 = king) and PieceType + 7 for Black (7-12).
+   */
+  public getBoardBytes(): Uint8Array {
+    const bytes = new Uint8Array(64);
+    for (let rank = 0; rank < 8; rank++) {
+      for (let file = 0; file < 8; file++) {
+        const piece = this.board[rank][file];
+        if (piece) {
+          bytes[rank * 8 + file] =
+            piece.type + (piece.color === Color.White ? 1 : 7);
+        }
+      }
+    }
+    return bytes;
+  }
+
+  /**
+   * The position in 38 bytes, for storing many positions or sharing them in
+   * URLs. Bytes 0-31 hold the board at 4 bits per square (getBoardBytes()
+   * codes, square 2i in the low nibble of byte i, 2i+1 in the high one).
+   * Byte 32 is the side to move (bit 0 set for Black) and castling rights
+   * (bits 1-4: K, Q, k, q); byte 33 is the en passant file plus one, or 0;
+   * bytes 34-35 and 36-37 are the halfmove clock and fullmove number as
+   * little-endian 16-bit integers. Read back with ChessRules.fromBytes().
+   */
+  public toBytes(): Uint8Array {
+    const bytes = new Uint8Array(ChessRules.POSITION_BYTES);
+    const board = this.getBoardBytes();
+    for (let i = 0; i < 32; i++) {
+      bytes[i] = board[2 * i] | (board[2 * i + 1] << 4);
+    }
+    const rights = this.castlingRights;
+    bytes[32] =
+      (this.currentPlayer === Color.Black ? 1 : 0) |
+      (rights.whiteKingSide ? 2 : 0) |
+      (rights.whiteQueenSide ? 4 : 0) |
+      (rights.blackKingSide ? 8 : 0) |
+      (rights.blackQueenSide ? 16 : 0);
+    bytes[33] = (this.getEnPassantFile() ?? -1) + 1;
+    const view = new DataView(bytes.buffer);
+    view.setUint16(34, Math.min(this.halfmoveClock, 0xffff), true);
+    view.setUint16(36, Math.min(this.fullmoveNumber, 0xffff), true);
+    return bytes;
+  }
+
+  /**
+   * Load a position written by toBytes() into a new engine. Returns null if
+   * the input has the wrong length or does not describe a valid position.
+   */
+  public static fromBytes(bytes: Uint8Array): ChessRules | null {
+    if (bytes.length !== ChessRules.POSITION_BYTES) return null;
+
+    const pieceChars = ' PRNBQKprnbqk';
+    let placement = '';
+    for (let rank = 7; rank >= 0; rank--) {
+      let empty = 0;
+      for (let file = 0; file < 8; file++) {
+        const index = rank * 8 + file;
+        const code = (bytes[index >> 1] >> ((index & 1) * 4)) & 0xf;
+        if (code > 12) return null;
+        if (code === 0) {
+          empty++;
+          continue;
+        }
+        if (empty > 0) placement += empty;
+        empty = 0;
+        placement += pieceChars[code];
+      }
+      if (empty > 0) placement += empty;
+      if (rank > 0) placement += '/';
+    }
+
+    const flags = bytes[32];
+    const black = (flags & 1) !== 0;
+    const castling =
+      [...'KQkq'].filter((_, i) => (flags & (2 << i)) !== 0).join('') || '-';
+    const epFile = bytes[33];
+    if (epFile > 8) return null;
+    const ep =
+      epFile === 0
+        ? '-'
+        : String.fromCharCode(96 + epFile) + (black ? '3' : '6');
+    const view = new DataView(bytes.buffer, bytes.byteOffset, bytes.length);
+    const fen = [
+      placement,
+      black ? 'b' : 'w',
+      castling,
+      ep,
+      view.getUint16(34, true),
+      view.getUint16(36, true),
+    ].join(' ');
+
+    const engine = new ChessRules();
+    return engine.setPosition(fen) ? engine : null;
+  }
+
+  private static readonly POSITION_BYTES = 38;
+
+  /**
+   * Squares attacked by at least one piece of `color`, as a 64-bit mask with
+   * bit `rank * 8 + file` set (bit 0 = a1, bit 63 = h8). Pawns contribute
+   * their capture diagonals, not their pushes.
+   */
+  public getControlledSquares(color: Color): bigint {
+    let mask = 0n;
+    for (let rank = 0; rank < 8; rank++) {
+      for (let file = 0; file < 8; file++) {
+        if (this.isSquareAttackedAt(file, rank, color)) {
+          mask |= 1n << BigInt(rank * 8 + file);
+        }
+      }
+    }
+    return mask;
+  }
+
+  /**
+   * Squares of every piece of `byColor` attacking `position`, cheapest
+   * attacker first (pawn, knight, bishop, rook, queen, king) — the order a
+   * capture sequence on that square would use them in.
+   */
+  public getAttackers(position: Position, byColor: Color): Position[] {
+    const order = [
+      PieceType.Pawn,
+      PieceType.Knight,
+      PieceType.Bishop,
+      PieceType.Rook,
+      PieceType.Queen,
+      PieceType.King,
+    ];
+    const rankOf = (p: Position) => order.indexOf(this.getPiece(p)!.type);
+    return this.findAttackers(position.file, position.rank, byColor).sort(
+      (a, b) => rankOf(a) - rankOf(b)
+    );
+  }
+
+  /**
+   * Both sides' attackers of `position` with their pieces, cheapest first —
+   * the order each side would capture on the square in, as
+   * staticExchange() assumes. For drawing a contested square's exchange.
+   */
+  public getExchangeParticipants(position: Position): ExchangeParticipants {
+    const withPieces = (color: Color) =>
+      this.getAttackers(position, color).map(p => ({
+        position: p,
+        piece: { ...this.getPiece(p)! },
+      }));
+    return { white: withPieces(Color.White), black: withPieces(Color.Black) };
+  }
+
+  /**
+   * Squares of the side to move's pieces that an enemy piece attacks right
+   * now, whether or not they are defended. Same order as getPieces().
+   */
+  public getThreatenedPieces(): Position[] {
+    const enemy =
+      this.currentPlayer === Color.White ? Color.Black : Color.White;
+    return this.getPieces()
+      .filter(
+        ({ position, piece }) =>
+          piece.color === this.currentPlayer &&
+          this.isSquareAttackedAt(position.file, position.rank, enemy)
+      )
+      .map(({ position }) => position);
+  }
+
   private getPieceFromBoard(
     position: Position,
     board: (Piece | null)[][]
@@ -212,7 +574,43 @@ export class ChessRules {
   public getValidMoves(from: Position): Position[] {
     const piece = this.getPiece(from);
     if (!piece || piece.color !== this.currentPlayer) return [];
+    return this.getLegalDestinations(from, piece, this.getPinsIfNotInCheck());
+  }
+
+  /**
+   * Legal moves of the piece on `from` as full Move objects, a promotion
+   * expanded into one move per piece (queen, rook, bishop, knight) so the UI
+   * knows to open the picker. Empty unless `from` holds a piece of the side
+   * to move; getValidMoves() stays the cheap call for highlighting.
+   */
+  public getValidMovesDetailed(from: Position): Move[] {
+    return this.generateLegalMoves().filter(
+      move => move.fromFile === from.file && move.fromRank === from.rank
+    );
+  }
+
+  /**
+   * Pins for the side to move, or null when it is in check. Computed once per
+   * position and shared by every piece's move generation.
+   */
+  private getPinsIfNotInCheck(): PinnedPiece[] | null {
+    return this.isKingInCheck(this.currentPlayer)
+      ? null
+      : this.getPinnedPieces();
+  }
 
+  /**
+   * Legal destinations for `piece` on `from`. Out of check (`pins` given), a
+   * non-king move can only expose the king if the piece is pinned, and then
+   * only by leaving the pin ray — so no make-and-test is needed. King moves,
+   * en passant (which can uncover a rank) and check evasions (`pins` null)
+   * still go through isMoveLegal().
+   */
+  private getLegalDestinations(
+    from: Position,
+    piece: Piece,
+    pins: PinnedPiece[] | null
+  ): Position[] {
     const pseudoLegalMoves = this.getValidMovesForPiece(
       from.file,
       from.rank,
@@ -220,10 +618,101 @@ export class ChessRules {
       true
     );
 
+    if (pins && piece.type !== PieceType.King) {
+      const same = (a: Position) => (b: Position) =>
+        a.file === b.file && a.rank === b.rank;
+      const pin = pins.find(p => same(from)(p.position));
+      return pseudoLegalMoves.filter(to => {
+        if (piece.type === PieceType.Pawn && this.isEnPassantSquare(to)) {
+          return this.isMoveLegal(from, to);
+        }
+        return !pin || pin.ray.some(same(to));
+      });
+    }
+
     // Filter out moves that would leave the king in check
     return pseudoLegalMoves.filter(to => this.isMoveLegal(from, to));
   }
 
+  /**
+   * Pieces of the side to move pinned to their king, each with the pinning
+   * piece and the ray it may still move along: the squares between king and
+   * pinner, plus the pinner itself.
+   */
+  public getPinnedPieces(): PinnedPiece[] {
+    const color = this.currentPlayer;
+    const king = this.findKing(color);
+    if (!king) return [];
+
+    const pins: PinnedPiece[] = [];
+    const rays: Array<[ReadonlyArray<readonly [number, number]>, PieceType]> = [
+      [ChessRules.ROOK_DIRECTIONS, PieceType.Rook],
+      [ChessRules.BISHOP_DIRECTIONS, PieceType.Bishop],
+    ];
+    for (const [directions, slider] of rays) {
+      for (const [df, dr] of directions) {
+        const ray: Position[] = [];
+        let own: Position | null = null;
+        let f = king.file + df;
+        let r = king.rank + dr;
+        while (this.isInBounds(f, r)) {
+          ray.push({ file: f, rank: r });
+          const p = this.board[r][f];
+          if (p) {
+            if (p.color === color) {
+              if (own) break; // Two of our pieces: no pin
+              own = { file: f, rank: r };
+            } else {
+              if (own && (p.type === slider || p.type === PieceType.Queen)) {
+                const pinnedBy = { file: f, rank: r };
+                pins.push({ position: own, pinnedBy, ray });
+              }
+              break;
+            }
+          }
+          f += df;
+          r += dr;
+        }
+      }
+    }
+    return pins;
+  }
+
+  private isEnPassantSquare(to: Position): boolean {
+    return (
+      this.enPassantTarget !== null &&
+      to.file === this.enPassantTarget.file &&
+      to.rank === this.enPassantTarget.rank
+    );
+  }
+
+  /**
+   * Number of legal moves for the side to move, counting each promotion
+   * choice separately. Equal to getGameState().validMoves.length, but does
+   * not build the Move list (or the rest of the game state) to get it.
+   */
+  public getLegalMoveCount(): number {
+    if (this.cachedGameState) return this.cachedGameState.validMoves.length;
+
+    let count = 0;
+    for (let rank = 0; rank < 8; rank++) {
+      for (let file = 0; file < 8; file++) {
+        const piece = this.board[rank][file];
+        if (!piece || piece.color !== this.currentPlayer) continue;
+        const promotionRank =
+          piece.type === PieceType.Pawn
+            ? piece.color === Color.White
+              ? 7
+              : 0
+            : -1;
+        for (const to of this.getValidMoves({ file, rank })) {
+          count += to.rank === promotionRank ? 4 : 1;
+        }
+      }
+    }
+    return count;
+  }
+
   /**
    * Get potential moves for UI hints and pre-moves.
    * This method can be configured to show different levels of move validation.
@@ -604,6 +1093,32 @@ export class ChessRules {
     return file >= 0 && file <= 7 && rank >= 0 && rank <= 7;
   }
 
+  /**
+   * Whether `color` could castle kingside right now: the right is intact,
+   * the squares between king and rook are empty, and the king is not in
+   * check and does not pass through or land on an attacked square.
+   */
+  public canCastleKingside(color: Color): boolean {
+    return this.isCastlingAvailable(color, true);
+  }
+
+  /** Queenside counterpart of canCastleKingside(). */
+  public canCastleQueenside(color: Color): boolean {
+    return this.isCastlingAvailable(color, false);
+  }
+
+  private isCastlingAvailable(color: Color, kingSide: boolean): boolean {
+    const rank = color === Color.White ? 0 : 7;
+    const king = this.board[rank][4];
+    if (!king || king.type !== PieceType.King || king.color !== color) {
+      return false;
+    }
+    const moves: Position[] = [];
+    this.addCastlingMoves({ file: 4, rank }, color, moves);
+    const targetFile = kingSide ? 6 : 2;
+    return moves.some(m => m.file === targetFile);
+  }
+
   private addCastlingMoves(
     from: Position,
     color: Color,
@@ -642,6 +1157,55 @@ export class ChessRules {
     }
   }
 
+  /**
+   * Why `color` cannot castle on the given side right now, for a precise UI
+   * message, or null if it can (turn aside). Conditions are checked in this
+   * order: "king has moved", "rook has moved", "squares between are
+   * occupied", "king is in check", "king would pass through check" and
+   * "king would end up in check". A lost right is blamed on the king when
+   * both rights of `color` are gone, otherwise on the rook.
+   */
+  public getCastleStatus(color: Color, kingSide: boolean): string | null {
+    const rank = color === Color.White ? 0 : 7;
+    const king = this.board[rank][4];
+    const rights = this.castlingRights;
+    const [kingSideRight, queenSideRight] =
+      color === Color.White
+        ? [rights.whiteKingSide, rights.whiteQueenSide]
+        : [rights.blackKingSide, rights.blackQueenSide];
+    if (
+      king?.type !== PieceType.King ||
+      king.color !== color ||
+      (!kingSideRight && !queenSideRight)
+    ) {
+      return 'king has moved';
+    }
+    const rook = this.board[rank][kingSide ? 7 : 0];
+    if (
+      !(kingSide ? kingSideRight : queenSideRight) ||
+      rook?.type !== PieceType.Rook ||
+      rook.color !== color
+    ) {
+      return 'rook has moved';
+    }
+
+    const between = kingSide ? [5, 6] : [1, 2, 3];
+    if (between.some(file => this.board[rank][file] !== null)) {
+      return 'squares between are occupied';
+    }
+    if (this.isKingInCheckAt(color, 4, rank)) return 'king is in check';
+    if (this.canCastle(color, kingSide)) return null;
+    // canCastle() failed on an attacked square; find out which
+    this.board[rank][4] = null;
+    try {
+      return this.isKingInCheckAt(color, kingSide ? 5 : 3, rank)
+        ? 'king would pass through check'
+        : 'king would end up in check';
+    } finally {
+      this.board[rank][4] = king;
+    }
+  }
+
   private canCastle(color: Color, kingSide: boolean): boolean {
     const rank = color === Color.White ? 0 : 7;
     const kingFile = 4;
@@ -691,7 +1255,7 @@ export class ChessRules {
 
   private isKingInCheckAt(color: Color, file: number, rank: number): boolean {
     const enemyColor = color === Color.White ? Color.Black : Color.White;
-    return this.isSquareAttacked(file, rank, enemyColor);
+    return this.isSquareAttackedAt(file, rank, enemyColor);
   }
 
   private static readonly KNIGHT_OFFSETS: ReadonlyArray<
@@ -738,12 +1302,21 @@ export class ChessRules {
     [-1, -1],
   ];
 
+  /**
+   * Whether any piece of `byColor` attacks `position`, whoever stands on it.
+   * Pawns attack diagonally forward only and sliders stop at the first
+   * blocker. For attack overlays in analysis UIs.
+   */
+  public isSquareAttacked(position: Position, byColor: Color): boolean {
+    return this.isSquareAttackedAt(position.file, position.rank, byColor);
+  }
+
   /**
    * Test whether a square is attacked by any piece of the given color, by
    * probing outward from the square (knight/king/pawn offsets plus sliding
    * rays) instead of generating every enemy piece's move list.
    */
-  private isSquareAttacked(
+  private isSquareAttackedAt(
     file: number,
     rank: number,
     byColor: Color
@@ -811,6 +1384,56 @@ export class ChessRules {
     return false;
   }
 
+  /**
+   * Squares of every piece of `byColor` attacking the given square. Same
+   * probing as isSquareAttackedAt(), but collects instead of short-circuiting.
+   */
+  private findAttackers(
+    file: number,
+    rank: number,
+    byColor: Color
+  ): Position[] {
+    const attackers: Position[] = [];
+    const probe = (f: number, r: number, types: PieceType[]) => {
+      const p = this.getPiece({ file: f, rank: r });
+      if (p && p.color === byColor && types.includes(p.type)) {
+        attackers.push({ file: f, rank: r });
+      }
+    };
+
+    for (const [df, dr] of ChessRules.KNIGHT_OFFSETS) {
+      probe(file + df, rank + dr, [PieceType.Knight]);
+    }
+    for (const [df, dr] of ChessRules.KING_OFFSETS) {
+      probe(file + df, rank + dr, [PieceType.King]);
+    }
+    const pawnRank = byColor === Color.White ? rank - 1 : rank + 1;
+    for (const df of [-1, 1]) {
+      probe(file + df, pawnRank, [PieceType.Pawn]);
+    }
+
+    const rays: Array<[ReadonlyArray<readonly [number, number]>, PieceType]> = [
+      [ChessRules.ROOK_DIRECTIONS, PieceType.Rook],
+      [ChessRules.BISHOP_DIRECTIONS, PieceType.Bishop],
+    ];
+    for (const [directions, slider] of rays) {
+      for (const [df, dr] of directions) {
+        let f = file + df;
+        let r = rank + dr;
+        while (this.isInBounds(f, r)) {
+          if (this.board[r][f]) {
+            probe(f, r, [slider, PieceType.Queen]);
+            break;
+          }
+          f += df;
+          r += dr;
+        }
+      }
+    }
+
+    return attackers;
+  }
+
   /**
    * Get movement pattern for a piece, with optional piece blocking consideration.
    * This unifies the logic used by both legal move validation and pre-move hints.
@@ -1009,6 +1632,12 @@ export class ChessRules {
       capturedPiece = targetPiece;
     }
 
+    // A promotion piece only makes sense on a promotion. Accepting and
+    // ignoring one elsewhere would let a bogus move like e2-e4=Q through.
+    if (type !== 'promotion' && promotionPiece !== undefined) {
+      return { valid: false };
+    }
+
     return {
       valid: true,
       type,
@@ -1026,22 +1655,149 @@ export class ChessRules {
     return this.analyzeMoveType(from, to, promotionPiece);
   }
 
-  public makeMove(
-    from: Position,
-    to: Position,
-    promotionPiece?: PieceType
-  ): MoveResult {
-    // First analyze the move to get rich information. A promotion move
-    // without a promotion piece is valid-but-incomplete: report it as
-    // promotionRequired instead of executing with an undefined piece.
-    const analysis = this.analyzeMoveType(from, to, promotionPiece);
-    if (!analysis.valid || analysis.promotionRequired) {
-      return {
-        success: false,
-        promotionRequired: analysis.promotionRequired,
-      };
-    }
-
+  /**
+   * Classify `move` against the current position without making it — e.g. to
+   * pick a sound effect when a piece is dropped. Only the shape of the move
+   * is examined, not its legality. Returns null if `from` is empty.
+   */
+  public classifyMove(move: Move): MoveClassification | null {
+    const piece = this.getPiece({ file: move.fromFile, rank: move.fromRank });
+    if (!piece) return null;
+
+    const target = this.getPiece({ file: move.toFile, rank: move.toRank });
+    const isPawn = piece.type === PieceType.Pawn;
+    const isEnPassant =
+      isPawn &&
+      !target &&
+      move.fromFile !== move.toFile &&
+      this.enPassantTarget !== null &&
+      this.enPassantTarget.file === move.toFile &&
+      this.enPassantTarget.rank === move.toRank;
+
+    return {
+      isCapture: (!!target && target.color !== piece.color) || isEnPassant,
+      isCastle:
+        piece.type === PieceType.King &&
+        Math.abs(move.toFile - move.fromFile) === 2,
+      isEnPassant,
+      isPromotion:
+        isPawn && move.toRank === (piece.color === Color.White ? 7 : 0),
+      isDoublePush:
+        isPawn &&
+        move.fromFile === move.toFile &&
+        Math.abs(move.toRank - move.fromRank) === 2,
+    };
+  }
+
+  /**
+   * Whether `move` gives check and how: 'direct' when the moved piece checks
+   * (for castling, the rook), 'discovered' when a piece it uncovered does,
+   * 'double' when two pieces do. Returns null if the move is not legal here.
+   */
+  public getCheckType(move: Move): CheckType | null {
+    const from = { file: move.fromFile, rank: move.fromRank };
+    const to = { file: move.toFile, rank: move.toRank };
+    const probe = this.clone();
+    const result = probe.makeMove(from, to, move.promotionPiece);
+    if (!result.success) return null;
+
+    const king = probe.findKing(probe.currentPlayer);
+    if (!king) return 'none';
+    const checkers = probe.findAttackers(
+      king.file,
+      king.rank,
+      this.currentPlayer
+    );
+    if (checkers.length === 0) return 'none';
+    if (checkers.length > 1) return 'double';
+
+    const moved = [to, ...(result.additionalMoves ?? []).map(m => m.to)];
+    const [checker] = checkers;
+    return moved.some(p => p.file === checker.file && p.rank === checker.rank)
+      ? 'direct'
+      : 'discovered';
+  }
+
+  /**
+   * Whether `move` leaves the opponent's king in check, by the moved piece
+   * or a discovered attacker (see getCheckType() for which). Returns null if
+   * the move is not legal here.
+   */
+  public givesCheck(move: Move): boolean | null {
+    const type = this.getCheckType(move);
+    return type === null ? null : type !== 'none';
+  }
+
+  /**
+   * True if moving the piece on `from` to `to` would be a promotion: a pawn
+   * of the side to move heading for the last rank. Lets the UI decide to
+   * open the promotion picker before it has a complete move.
+   */
+  public isPromotionMove(from: Position, to: Position): boolean {
+    const piece = this.getPiece(from);
+    if (
+      !piece ||
+      piece.type !== PieceType.Pawn ||
+      piece.color !== this.currentPlayer
+    ) {
+      return false;
+    }
+    return to.rank === (piece.color === Color.White ? 7 : 0);
+  }
+
+  /**
+   * Promotion pieces the picker may offer for the pawn move `from` -> `to`.
+   * All four are legal whenever the move itself is, so this is either
+   * [Queen, Rook, Bishop, Knight] or empty (not a promotion, or illegal —
+   * e.g. the pawn is pinned or the move leaves the king in check).
+   */
+  public getLegalPromotions(from: Position, to: Position): PieceType[] {
+    if (!this.isPromotionMove(from, to)) return [];
+    const legal = this.getValidMoves(from).some(
+      move => move.file === to.file && move.rank === to.rank
+    );
+    return legal
+      ? [PieceType.Queen, PieceType.Rook, PieceType.Bishop, PieceType.Knight]
+      : [];
+  }
+
+  public makeMove(
+    from: Position,
+    to: Position,
+    promotionPiece?: PieceType
+  ): MoveResult {
+    // Kings are never captured. Only free play can get here, letting the
+    // side not to move take a king left in check.
+    if (this.getPiece(to)?.type === PieceType.King) {
+      return { success: false };
+    }
+
+    // Saved before a free-play turn switch so undo restores the real turn
+    const { currentPlayer, enPassantTarget } = this;
+    const owner = this.getPiece(from)?.color;
+    const switchTurn =
+      this.freePlay && owner !== undefined && owner !== this.currentPlayer;
+    if (switchTurn) {
+      // The en passant target belongs to the side whose turn is skipped
+      this.currentPlayer = owner;
+      this.enPassantTarget = null;
+    }
+
+    // First analyze the move to get rich information. A promotion move
+    // without a promotion piece is valid-but-incomplete: report it as
+    // promotionRequired instead of executing with an undefined piece.
+    const analysis = this.analyzeMoveType(from, to, promotionPiece);
+    if (!analysis.valid || analysis.promotionRequired) {
+      if (switchTurn) {
+        this.currentPlayer = currentPlayer;
+        this.enPassantTarget = enPassantTarget;
+      }
+      return {
+        success: false,
+        promotionRequired: analysis.promotionRequired,
+      };
+    }
+
     const piece = this.getPiece(from)!; // We know it exists from analysis
     const { type, capturedPiece, additionalMoves } = analysis;
 
@@ -1049,6 +1805,10 @@ export class ChessRules {
     // it now, before the move is executed.
     const disambiguation = this.computeDisambiguation(piece, from, to);
 
+    const snapshot = { ...this.snapshot(), currentPlayer, enPassantTarget };
+    this.undoStack.push(snapshot);
+    this.resignedBy = null;
+
     // Clear en passant target from previous turn
     this.enPassantTarget = null;
 
@@ -1190,6 +1950,35 @@ export class ChessRules {
     });
     this.fenHistory.push(fen);
 
+    // Every square whose contents differ from the pre-move board — two for
+    // an ordinary move, three for en passant, four for castling
+    const ply = this.historyEntries.length;
+    for (let rank = 0; rank < 8; rank++) {
+      for (let file = 0; file < 8; file++) {
+        if (this.board[rank][file] !== snapshot.board[rank][file]) {
+          this.squareChangedAt[rank][file] = ply;
+        }
+      }
+    }
+
+    // Replaying the next move of a game navigated with gotoPly() keeps the
+    // rest of it; any other move starts a new line
+    const next = this.redoEntries[0]?.move;
+    if (
+      next &&
+      next.fromFile === moveRecord.fromFile &&
+      next.fromRank === moveRecord.fromRank &&
+      next.toFile === moveRecord.toFile &&
+      next.toRank === moveRecord.toRank &&
+      next.promotionPiece === moveRecord.promotionPiece
+    ) {
+      this.redoEntries = this.redoEntries.slice(1);
+    } else {
+      this.redoEntries = [];
+    }
+
+    this.notifyMove(moveRecord, fen, false);
+
     return {
       success: true,
       type,
@@ -1198,17 +1987,262 @@ export class ChessRules {
     };
   }
 
-  public isKingInCheck(color: Color): boolean {
-    // Find the king of the specified color
-    for (let rank = 0; rank < 8; rank++) {
-      for (let file = 0; file < 8; file++) {
-        const piece = this.board[rank][file];
-        if (piece && piece.type === PieceType.King && piece.color === color) {
-          return this.isKingInCheckAt(color, file, rank);
-        }
+  /**
+   * Pass the turn without moving (for analysis and null-move search).
+   * Clears the en passant target and can be reverted with undoMove().
+   * Refused while the side to move is in check.
+   */
+  public makeNullMove(): boolean {
+    if (this.isKingInCheck(this.currentPlayer)) return false;
+
+    this.undoStack.push(this.snapshot());
+    this.enPassantTarget = null;
+    this.halfmoveClock++;
+    if (this.currentPlayer === Color.Black) this.fullmoveNumber++;
+    this.currentPlayer =
+      this.currentPlayer === Color.White ? Color.Black : Color.White;
+    this.cachedGameState = null;
+    return true;
+  }
+
+  /**
+   * Take back the last move or null move, restoring the previous position
+   * exactly. Returns false if there is nothing to undo.
+   */
+  public undoMove(): boolean {
+    const snapshot = this.undoStack.pop();
+    if (!snapshot) return false;
+    this.resignedBy = null;
+    // Null moves have no history entry and are not reported to onMove
+    const undoneMove =
+      this.moveHistory.length > snapshot.historyLength
+        ? this.moveHistory[snapshot.historyLength]
+        : null;
+
+    this.board = snapshot.board;
+    this.currentPlayer = snapshot.currentPlayer;
+    this.enPassantTarget = snapshot.enPassantTarget;
+    this.lastMove = snapshot.lastMove;
+    this.castlingRights = snapshot.castlingRights;
+    this.squareChangedAt = snapshot.squareChangedAt;
+    this.halfmoveClock = snapshot.halfmoveClock;
+    this.fullmoveNumber = snapshot.fullmoveNumber;
+    if (undoneMove) {
+      const entry = this.historyEntries[snapshot.historyLength];
+      this.redoEntries = [entry, ...this.redoEntries];
+    }
+    // Slice rather than truncate in place: moveHistory is shared with
+    // previously returned GameState objects.
+    this.moveHistory = this.moveHistory.slice(0, snapshot.historyLength);
+    this.historyEntries = this.historyEntries.slice(0, snapshot.historyLength);
+    this.fenHistory = this.fenHistory.slice(0, snapshot.historyLength + 1);
+    this.cachedGameState = null;
+    if (undoneMove) this.notifyMove(undoneMove, this.generateFEN(), true);
+    return true;
+  }
+
+  /**
+   * In free play, makeMove() accepts a legal move by either side: if the
+   * piece belongs to the side not to move, the turn passes to its owner
+   * first, then to the opponent as usual. Moves must still be legal for the
+   * moving side. For entering analysis lines by hand; off by default.
+   */
+  public setFreePlay(enabled: boolean): void {
+    this.freePlay = enabled;
+  }
+
+  /**
+   * Set the en passant square for the next move — the square a pawn just
+   * skipped over — or clear it with null. For the position editor and
+   * mid-game setup. The square must pass the same checks as the FEN en
+   * passant field (see enPassantTargetError()); otherwise returns false and
+   * leaves the target unchanged.
+   */
+  public setEnPassantTarget(target: Position | null): boolean {
+    if (
+      target &&
+      ChessRules.enPassantTargetError(this.board, this.currentPlayer, target)
+    ) {
+      return false;
+    }
+    this.enPassantTarget = target ? { ...target } : null;
+    this.cachedGameState = null;
+    this.fenHistory[this.fenHistory.length - 1] = this.generateFEN();
+    return true;
+  }
+
+  /**
+   * Why `target` cannot be the en passant square with `side` to move on
+   * `board`, or null if it can: it must be empty, on rank 6 with White to
+   * move or rank 3 with Black to move, with an enemy pawn in front of it
+   * (the pawn that just made its double step).
+   */
+  private static enPassantTargetError(
+    board: (Piece | null)[][],
+    side: Color,
+    target: Position
+  ): string | null {
+    const { file, rank } = target;
+    if (file < 0 || file > 7 || rank < 0 || rank > 7) return 'off the board';
+    const white = side === Color.White;
+    if (rank !== (white ? 5 : 2)) {
+      return white
+        ? 'must be on rank 6 with White to move'
+        : 'must be on rank 3 with Black to move';
+    }
+    if (board[rank][file] !== null) return 'the square is occupied';
+    const pawn = board[white ? rank - 1 : rank + 1][file];
+    if (pawn?.type !== PieceType.Pawn || pawn.color === side) {
+      return 'no enemy pawn in front of it';
+    }
+    return null;
+  }
+
+  /**
+   * Half-moves since the last capture or pawn move, as in the FEN — the
+   * count the fifty-move rule is judged on.
+   */
+  public getHalfmoveClock(): number {
+    return this.halfmoveClock;
+  }
+
+  /** The FEN move number: starts at 1 and goes up after each Black move. */
+  public getFullmoveNumber(): number {
+    return this.fullmoveNumber;
+  }
+
+  /** Number of moves (plies) played to reach the current position. */
+  public getCurrentPly(): number {
+    return this.historyEntries.length;
+  }
+
+  /**
+   * Move through the game for review: show the position after `ply` moves
+   * (0 = the starting position), replaying from the starting FEN. Moves
+   * after `ply` are kept, so later calls can go forward again; making a
+   * different move from there discards them. Comments and NAGs survive,
+   * the setOnMove() callback is not called, and null moves are dropped.
+   * Returns false, leaving the game as it was, if `ply` is outside 0 to the
+   * length of the whole game, the starting position cannot be loaded (as
+   * for an empty() board) or a move no longer replays (e.g. one played out
+   * of turn with free play that has since been switched off).
+   */
+  public gotoPly(ply: number): boolean {
+    const entries = [...this.historyEntries, ...this.redoEntries];
+    if (!Number.isInteger(ply) || ply < 0 || ply > entries.length) {
+      return false;
+    }
+
+    const startFen = this.fenHistory[0];
+    const replay = (engine: ChessRules) => {
+      if (!engine.setPosition(startFen)) return false;
+      for (const { move } of entries.slice(0, ply)) {
+        const result = engine.makeMove(
+          { file: move.fromFile, rank: move.fromRank },
+          { file: move.toFile, rank: move.toRank },
+          move.promotionPiece
+        );
+        if (!result.success) return false;
       }
+      return true;
+    };
+    // Try it on a copy first so a failed replay leaves this game untouched
+    if (!replay(this.clone())) return false;
+
+    const { onMove, resignedBy } = this;
+    this.onMove = null;
+    replay(this);
+    this.historyEntries = entries.slice(0, ply);
+    this.redoEntries = entries.slice(ply);
+    this.resignedBy = resignedBy;
+    this.onMove = onMove;
+    return true;
+  }
+
+  /**
+   * Ply (1 = the first move of the game) at which the contents of `position`
+   * last changed, or 0 if the square is as it was at the start. Castling and
+   * en passant update every square they touch; undoMove() rolls back.
+   */
+  public getSquareLastChanged(position: Position): number {
+    if (!this.isInBounds(position.file, position.rank)) return 0;
+    return this.squareChangedAt[position.rank][position.file];
+  }
+
+  private static createUnchangedSquares(): number[][] {
+    return Array.from({ length: 8 }, () => Array(8).fill(0));
+  }
+
+  /**
+   * Register a callback to run after each makeMove() and undoMove(), or pass
+   * null to remove it. Copies made with clone() start without one.
+   */
+  public setOnMove(callback: MoveCallback | null): void {
+    this.onMove = callback;
+  }
+
+  private notifyMove(move: Move, fen: string, undone: boolean): void {
+    if (!this.onMove) return;
+    try {
+      this.onMove({ ...move }, fen, undone);
+    } catch {
+      // The move is already applied — a failing listener must not turn it
+      // into an exception for the caller or leave the engine half-updated.
     }
-    return false;
+  }
+
+  /**
+   * An independent copy of this engine — position, history, undo stack and
+   * piece values — for exploring a variation without touching the original.
+   */
+  public clone(): ChessRules {
+    const copy = new ChessRules();
+    copy.board = this.board.map(row => [...row]);
+    copy.currentPlayer = this.currentPlayer;
+    copy.enPassantTarget = this.enPassantTarget;
+    copy.lastMove = this.lastMove;
+    copy.moveHistory = [...this.moveHistory];
+    copy.historyEntries = [...this.historyEntries];
+    copy.fenHistory = [...this.fenHistory];
+    copy.halfmoveClock = this.halfmoveClock;
+    copy.fullmoveNumber = this.fullmoveNumber;
+    copy.castlingRights = { ...this.castlingRights };
+    // Snapshots are restored by reference on undo, so each copy needs its own
+    copy.undoStack = this.undoStack.map(snapshot => ({
+      ...snapshot,
+      board: snapshot.board.map(row => [...row]),
+      castlingRights: { ...snapshot.castlingRights },
+      squareChangedAt: snapshot.squareChangedAt.map(row => [...row]),
+    }));
+    copy.squareChangedAt = this.squareChangedAt.map(row => [...row]);
+    copy.pieceValues = { ...this.pieceValues };
+    copy.resignedBy = this.resignedBy;
+    copy.freePlay = this.freePlay;
+    copy.redoEntries = [...this.redoEntries];
+    return copy;
+  }
+
+  private snapshot(): UndoSnapshot {
+    return {
+      board: this.board.map(row => [...row]),
+      currentPlayer: this.currentPlayer,
+      enPassantTarget: this.enPassantTarget,
+      lastMove: this.lastMove,
+      castlingRights: { ...this.castlingRights },
+      halfmoveClock: this.halfmoveClock,
+      fullmoveNumber: this.fullmoveNumber,
+      historyLength: this.historyEntries.length,
+      squareChangedAt: this.squareChangedAt.map(row => [...row]),
+    };
+  }
+
+  /**
+   * Whether `color`'s king is attacked. False when `color` has no king, as
+   * on a board still being set up.
+   */
+  public isKingInCheck(color: Color): boolean {
+    const king = this.findKing(color);
+    return king !== null && this.isKingInCheckAt(color, king.file, king.rank);
   }
 
   private updateCastlingRights(
@@ -1268,6 +2302,18 @@ export class ChessRules {
     return [...this.fenHistory];
   }
 
+  /**
+   * Pieces of `color` captured so far, in capture order, read from the move
+   * history. Unlike GameState.capturedPieces, which diffs against the
+   * starting material, a captured promoted piece counts as what it was on
+   * the board. Only covers moves played since the last setPosition().
+   */
+  public getCapturedPieces(color: Color): PieceType[] {
+    return this.historyEntries
+      .filter(entry => entry.capturedPiece?.color === color)
+      .map(entry => entry.capturedPiece!.type);
+  }
+
   public undoToFen(fen: string, plyCount: number): boolean {
     // Save history before setPosition (which clears it)
     const savedHistory = this.historyEntries.slice(0, plyCount);
@@ -1382,105 +2428,610 @@ export class ChessRules {
   }
 
   /**
-   * Count how many times the current position has occurred in the game.
+   * Count how many times the current position has occurred in the game,
+   * including this occurrence — 1 the first time it is reached.
    * Compares piece placement, active color, castling rights, and en passant target.
    */
   public getRepetitionCount(): number {
-    if (this.fenHistory.length === 0) return 1;
-    const currentKey = this.positionKey(this.generateFEN());
+    const currentKey = this.getFenEPD();
     let count = 0;
     for (const fen of this.fenHistory) {
       if (this.positionKey(fen) === currentKey) count++;
     }
+    // A position reached by a null move is not in fenHistory
+    const last = this.fenHistory[this.fenHistory.length - 1];
+    if (!last || this.positionKey(last) !== currentKey) count++;
     return count;
   }
 
-  /** Extract the position-relevant part of a FEN (no move counters) */
-  private positionKey(fen: string): string {
-    const parts = fen.split(' ');
-    // piece placement + active color + castling + en passant
-    return parts.slice(0, 4).join(' ');
-  }
-
   /**
-   * Check if the position has insufficient material for either side to checkmate.
-   * Only returns true when checkmate is literally impossible:
-   * - K vs K
-   * - K+B vs K
-   * - K+N vs K
-   * - K+B vs K+B (same color bishops)
+   * The key used for repetition detection: piece placement, side to move,
+   * castling rights and en passant square, without the move counters.
+   * Clients doing their own repetition tracking should bucket on this.
    */
-  public isInsufficientMaterial(): boolean {
-    const pieces: {
-      color: Color;
-      type: PieceType;
-      file: number;
-      rank: number;
-    }[] = [];
+  public getPositionKey(): string {
+    return this.getFenEPD();
+  }
 
-    for (let rank = 0; rank < 8; rank++) {
+  /** The piece-placement field of the current FEN, e.g. "8/8/8/4k3/...". */
+  public getFenPlacement(): string {
+    let placement = '';
+
+    for (let rank = 7; rank >= 0; rank--) {
+      let emptySquares = 0;
       for (let file = 0; file < 8; file++) {
         const piece = this.board[rank][file];
         if (piece) {
-          pieces.push({ ...piece, file, rank });
+          if (emptySquares > 0) {
+            placement += emptySquares.toString();
+            emptySquares = 0;
+          }
+          placement += this.pieceToFENChar(piece);
+        } else {
+          emptySquares++;
         }
       }
+      if (emptySquares > 0) {
+        placement += emptySquares.toString();
+      }
+      if (rank > 0) {
+        placement += '/';
+      }
     }
 
-    // Any pawns, rooks, or queens → sufficient material
-    if (
-      pieces.some(
-        p =>
-          p.type === PieceType.Pawn ||
-          p.type === PieceType.Rook ||
-          p.type === PieceType.Queen
-      )
-    ) {
-      return false;
-    }
+    return placement;
+  }
 
-    // Filter to non-king pieces
-    const minors = pieces.filter(p => p.type !== PieceType.King);
+  /**
+   * The first four FEN fields (placement, side to move, castling rights and
+   * en passant square) — the position as EPD, without move counters. Two
+   * positions that differ only in their clocks share the same value.
+   */
+  public getFenEPD(): string {
+    let epd = this.getFenPlacement();
 
-    // K vs K
-    if (minors.length === 0) return true;
+    // Add current player
+    epd += ` ${this.currentPlayer === Color.White ? 'w' : 'b'}`;
 
-    // K+minor vs K
-    if (minors.length === 1) return true;
+    // Add castling rights
+    let castling = '';
+    if (this.castlingRights.whiteKingSide) castling += 'K';
+    if (this.castlingRights.whiteQueenSide) castling += 'Q';
+    if (this.castlingRights.blackKingSide) castling += 'k';
+    if (this.castlingRights.blackQueenSide) castling += 'q';
+    epd += ` ${castling || '-'}`;
 
-    // K+B vs K+B — only insufficient if bishops are on the same color square
-    if (
-      minors.length === 2 &&
-      minors[0].type === PieceType.Bishop &&
-      minors[1].type === PieceType.Bishop &&
-      minors[0].color !== minors[1].color
-    ) {
-      const color0 = (minors[0].file + minors[0].rank) % 2;
-      const color1 = (minors[1].file + minors[1].rank) % 2;
-      if (color0 === color1) return true;
+    // Add en passant target square. makeMove() only sets it when a capture
+    // is possible, but a loaded FEN may carry a target no pawn can use —
+    // emit '-' for it so FENs and repetition keys match other engines.
+    if (this.enPassantTarget && this.canCaptureEnPassant()) {
+      const fileChar = String.fromCharCode(97 + this.enPassantTarget.file); // 'a' + file
+      const rankChar = (this.enPassantTarget.rank + 1).toString(); // rank + 1 for 1-based
+      epd += ` ${fileChar}${rankChar}`;
+    } else {
+      epd += ' -';
     }
 
-    return false;
+    return epd;
   }
 
   /**
-   * Parse a SAN (Standard Algebraic Notation) move and find the matching legal move.
-   * Returns the matching Move or null if no legal move matches.
+   * A position key shared by every position equivalent to this one under the
+   * board's symmetries: the colour flip (board mirrored top to bottom, piece
+   * colours, side to move and castling rights swapped) and, when neither
+   * side can castle, the left-right mirror. The smallest of the EPD keys of
+   * the equivalent positions is returned. Meant for deduplicating puzzles.
    */
-  public parseSAN(san: string): Move | null {
-    const FILE_CHARS = 'abcdefgh';
-    const cleaned = san.replace(/[+#!?]+$/, ''); // strip check/mate/annotation markers
+  public getCanonicalKey(): string {
+    const FILE_LETTERS = 'abcdefgh';
+    const swapCase = (text: string) =>
+      text.replace(/[a-z]/gi, c =>
+        c === c.toUpperCase() ? c.toLowerCase() : c.toUpperCase()
+      );
 
-    // Castling
-    if (cleaned === 'O-O' || cleaned === '0-0') {
-      const rank = this.currentPlayer === Color.White ? 0 : 7;
-      return this.isValidMove({ file: 4, rank }, { file: 6, rank }).valid
-        ? { fromFile: 4, fromRank: rank, toFile: 6, toRank: rank }
-        : null;
+    const colorFlip = (epd: string) => {
+      const [placement, side, castling, ep] = epd.split(' ');
+      const rights = swapCase(castling);
+      return [
+        swapCase(placement.split('/').reverse().join('/')),
+        side === 'w' ? 'b' : 'w',
+        castling === '-'
+          ? '-'
+          : [...'KQkq'].filter(c => rights.includes(c)).join(''),
+        ep === '-' ? '-' : ep[0] + (9 - parseInt(ep[1])),
+      ].join(' ');
+    };
+    // Runs of empty squares are single digits, so reversing each rank's
+    // text mirrors it
+    const mirror = (epd: string) => {
+      const [placement, side, castling, ep] = epd.split(' ');
+      return [
+        placement
+          .split('/')
+          .map(row => [...row].reverse().join(''))
+          .join('/'),
+        side,
+        castling,
+        ep === '-'
+          ? '-'
+          : FILE_LETTERS[7 - FILE_LETTERS.indexOf(ep[0])] + ep[1],
+      ].join(' ');
+    };
+
+    const key = this.getFenEPD();
+    const keys = [key, colorFlip(key)];
+    if (key.split(' ')[2] === '-') {
+      keys.push(...keys.map(mirror));
     }
-    if (cleaned === 'O-O-O' || cleaned === '0-0-0') {
-      const rank = this.currentPlayer === Color.White ? 0 : 7;
-      return this.isValidMove({ file: 4, rank }, { file: 2, rank }).valid
+    return keys.reduce((min, k) => (k < min ? k : min));
+  }
+
+  /**
+   * A 64-bit FNV-1a hash of the game's moves in UCI form, for caching
+   * analysis of whole games and spotting duplicate uploads. Equal move
+   * sequences hash equally; different move orders reaching the same
+   * position do not. Null moves are not part of the history.
+   */
+  public getGameHash(): bigint {
+    const MASK = (1n << 64n) - 1n;
+    const PRIME = 0x100000001b3n;
+    let hash = 0xcbf29ce484222325n;
+    for (const move of this.moveHistory) {
+      // Separate moves so "e2e4" + "e7e5" differs from any other split
+      for (const c of this.formatUCI(move) + ' ') {
+        hash = ((hash ^ BigInt(c.charCodeAt(0))) * PRIME) & MASK;
+      }
+    }
+    return hash;
+  }
+
+  /** Extract the position-relevant part of a FEN (no move counters) */
+  private positionKey(fen: string): string {
+    const parts = fen.split(' ');
+    // piece placement + active color + castling + en passant
+    return parts.slice(0, 4).join(' ');
+  }
+
+  /**
+   * Name the checkmate pattern on the board, if it is a well-known one:
+   * smothered mate (a knight mates a king boxed in by its own pieces),
+   * back-rank mate (a rook or queen mates along the back rank while the
+   * king's own pieces block its escape) or Anastasia's mate (a rook or queen
+   * mates along an edge file while a knight covers the escape squares).
+   * Returns null if the position is not checkmate or the mate is
+   * unclassified.
+   */
+  public getMatePattern(): MatePattern | null {
+    const state = this.getGameState();
+    if (state.result?.reason !== 'checkmate') return null;
+
+    const color = this.currentPlayer;
+    const enemy = color === Color.White ? Color.Black : Color.White;
+    const king = this.findKing(color);
+    if (!king) return null;
+
+    const checkers = this.findAttackers(king.file, king.rank, enemy);
+    if (checkers.length !== 1) return null;
+    const checker = checkers[0];
+    const checkerType = this.getPiece(checker)!.type;
+    const isHeavy =
+      checkerType === PieceType.Rook || checkerType === PieceType.Queen;
+
+    const neighbours: Position[] = [];
+    for (const [df, dr] of ChessRules.KING_OFFSETS) {
+      if (this.isInBounds(king.file + df, king.rank + dr)) {
+        neighbours.push({ file: king.file + df, rank: king.rank + dr });
+      }
+    }
+    const ownPieceOn = (sq: Position) => this.getPiece(sq)?.color === color;
+
+    if (checkerType === PieceType.Knight && neighbours.every(ownPieceOn)) {
+      return 'smothered';
+    }
+
+    const backRank = color === Color.White ? 0 : 7;
+    if (isHeavy && king.rank === backRank && checker.rank === backRank) {
+      const forward = neighbours.filter(sq => sq.rank !== backRank);
+      if (forward.some(ownPieceOn)) return 'back_rank';
+    }
+
+    if (
+      isHeavy &&
+      (king.file === 0 || king.file === 7) &&
+      checker.file === king.file
+    ) {
+      const inner = neighbours.filter(sq => sq.file !== king.file);
+      const knightCovers = inner.some(sq =>
+        this.findAttackers(sq.file, sq.rank, enemy).some(
+          a => this.getPiece(a)!.type === PieceType.Knight
+        )
+      );
+      if (knightCovers) return 'anastasia';
+    }
+
+    return null;
+  }
+
+  /**
+   * Squares where interposing a piece would block the check on the side to
+   * move, nearest the king first. Empty when not in check, in double check,
+   * or when the checker is a knight, pawn or adjacent piece.
+   */
+  public getCheckBlockSquares(): Position[] {
+    const king = this.findKing(this.currentPlayer);
+    if (!king) return [];
+    const enemy =
+      this.currentPlayer === Color.White ? Color.Black : Color.White;
+    const checkers = this.findAttackers(king.file, king.rank, enemy);
+    if (checkers.length !== 1) return [];
+    const checkerType = this.getPiece(checkers[0])!.type;
+    if (checkerType === PieceType.Knight || checkerType === PieceType.Pawn) {
+      return [];
+    }
+    return this.squaresBetween(king, checkers[0]);
+  }
+
+  /**
+   * Squares strictly between `a` and `b`, walking from `a`. Empty if the two
+   * squares do not share a rank, file or diagonal.
+   */
+  private squaresBetween(a: Position, b: Position): Position[] {
+    const df = Math.sign(b.file - a.file);
+    const dr = Math.sign(b.rank - a.rank);
+    const fileDistance = Math.abs(b.file - a.file);
+    const rankDistance = Math.abs(b.rank - a.rank);
+    if (
+      fileDistance !== 0 &&
+      rankDistance !== 0 &&
+      fileDistance !== rankDistance
+    ) {
+      return [];
+    }
+    const squares: Position[] = [];
+    let file = a.file + df;
+    let rank = a.rank + dr;
+    while (file !== b.file || rank !== b.rank) {
+      squares.push({ file, rank });
+      file += df;
+      rank += dr;
+    }
+    return squares;
+  }
+
+  private findKing(color: Color): Position | null {
+    for (let rank = 0; rank < 8; rank++) {
+      for (let file = 0; file < 8; file++) {
+        const piece = this.board[rank][file];
+        if (piece && piece.type === PieceType.King && piece.color === color) {
+          return { file, rank };
+        }
+      }
+    }
+    return null;
+  }
+
+  /**
+   * Override the centipawn values used for material counting (defaults are
+   * the classic 100/320/330/500/900). Useful for trainers and variants that
+   * want to weight pieces differently.
+   */
+  public setPieceValues(
+    pawn: number,
+    knight: number,
+    bishop: number,
+    rook: number,
+    queen: number
+  ): void {
+    this.pieceValues = {
+      [PieceType.Pawn]: pawn,
+      [PieceType.Knight]: knight,
+      [PieceType.Bishop]: bishop,
+      [PieceType.Rook]: rook,
+      [PieceType.Queen]: queen,
+      [PieceType.King]: 0,
+    };
+  }
+
+  /**
+   * Centipawn value of a piece type as used by this engine's material
+   * counting, including any setPieceValues() override. The king is never
+   * traded, so its value is 0 rather than a large sentinel — sort it
+   * separately if it needs to come first.
+   */
+  public getPieceValue(type: PieceType): number {
+    return this.pieceValues[type];
+  }
+
+  /** Default centipawn value of a piece type (king = 0, see getPieceValue). */
+  public static getBaseValue(type: PieceType): number {
+    return ChessRules.DEFAULT_PIECE_VALUES[type];
+  }
+
+  /** Material balance in centipawns from White's point of view. */
+  public getMaterialBalance(): number {
+    let balance = 0;
+    for (let rank = 0; rank < 8; rank++) {
+      for (let file = 0; file < 8; file++) {
+        const piece = this.board[rank][file];
+        if (!piece) continue;
+        const value = this.pieceValues[piece.type];
+        balance += piece.color === Color.White ? value : -value;
+      }
+    }
+    return balance;
+  }
+
+  /**
+   * Game phase from 24 (all pieces on the board) down to 0 (pawn endgame),
+   * based on remaining non-pawn material: knight/bishop = 1, rook = 2,
+   * queen = 4, summed over both sides and capped at 24.
+   */
+  public getGamePhase(): number {
+    const weights: Partial<Record<PieceType, number>> = {
+      [PieceType.Knight]: 1,
+      [PieceType.Bishop]: 1,
+      [PieceType.Rook]: 2,
+      [PieceType.Queen]: 4,
+    };
+    let phase = 0;
+    for (let rank = 0; rank < 8; rank++) {
+      for (let file = 0; file < 8; file++) {
+        const piece = this.board[rank][file];
+        if (piece) phase += weights[piece.type] ?? 0;
+      }
+    }
+    return Math.min(phase, 24);
+  }
+
+  /** Files (0-7) with no pawns of either color. */
+  public getOpenFiles(): number[] {
+    const [white, black] = this.countPawnsPerFile();
+    const files: number[] = [];
+    for (let file = 0; file < 8; file++) {
+      if (white[file] === 0 && black[file] === 0) files.push(file);
+    }
+    return files;
+  }
+
+  /** Files (0-7) with no pawns of `color` but at least one enemy pawn. */
+  public getHalfOpenFiles(color: Color): number[] {
+    const counts = this.countPawnsPerFile();
+    const own = counts[color];
+    const enemy = counts[color === Color.White ? Color.Black : Color.White];
+    const files: number[] = [];
+    for (let file = 0; file < 8; file++) {
+      if (own[file] === 0 && enemy[file] > 0) files.push(file);
+    }
+    return files;
+  }
+
+  /**
+   * How many of `color`'s knights and bishops stand off their starting
+   * squares (b/g and c/f files of the back rank) — "3/4 minors developed".
+   * Based on the current position only: a piece that went back home counts
+   * as undeveloped again.
+   */
+  public getDevelopedPieces(color: Color): number {
+    const backRank = color === Color.White ? 0 : 7;
+    const homeFiles: Partial<Record<PieceType, number[]>> = {
+      [PieceType.Knight]: [1, 6],
+      [PieceType.Bishop]: [2, 5],
+    };
+    return this.getPieces().filter(({ piece, position }) => {
+      const files = homeFiles[piece.type];
+      if (!files || piece.color !== color) return false;
+      return position.rank !== backRank || !files.includes(position.file);
+    }).length;
+  }
+
+  /**
+   * Whether `color`'s king looks castled: on g1/c1 (g8/c8 for Black) with
+   * its own rook beside it on f1/d1 (f8/d8). Like getDevelopedPieces(), this
+   * reads the position rather than the move history.
+   */
+  public hasCastled(color: Color): boolean {
+    const king = this.findKing(color);
+    const backRank = color === Color.White ? 0 : 7;
+    if (!king || king.rank !== backRank) return false;
+    const rookFile = king.file === 6 ? 5 : king.file === 2 ? 3 : -1;
+    if (rookFile === -1) return false;
+    const rook = this.board[backRank][rookFile];
+    return rook?.type === PieceType.Rook && rook.color === color;
+  }
+
+  /**
+   * Whether `color` has a back-rank weakness: its king is on its back rank
+   * with no escape square on the rank in front (each is blocked by its own
+   * piece or covered by the enemy), the opponent has a rook or queen, and
+   * some file has no pawn of `color` on it (open, or half-open for `color`),
+   * so that heavy piece has a way down to the back rank.
+   */
+  public isBackRankWeak(color: Color): boolean {
+    const king = this.findKing(color);
+    const backRank = color === Color.White ? 0 : 7;
+    if (!king || king.rank !== backRank) return false;
+
+    const enemy = color === Color.White ? Color.Black : Color.White;
+    const hasHeavyPiece = this.board.some(row =>
+      row.some(
+        p =>
+          p !== null &&
+          p.color === enemy &&
+          (p.type === PieceType.Rook || p.type === PieceType.Queen)
+      )
+    );
+    if (!hasHeavyPiece) return false;
+
+    const frontRank = color === Color.White ? 1 : 6;
+    for (let file = king.file - 1; file <= king.file + 1; file++) {
+      if (!this.isInBounds(file, frontRank)) continue;
+      const piece = this.board[frontRank][file];
+      if (piece?.color === color) continue;
+      if (!this.isSquareAttackedAt(file, frontRank, enemy)) return false;
+    }
+
+    return (
+      this.getOpenFiles().length > 0 ||
+      this.getHalfOpenFiles(color).length > 0
+    );
+  }
+
+  /** Pawn counts per file, indexed by color then file. */
+  private countPawnsPerFile(): [number[], number[]] {
+    const counts: [number[], number[]] = [Array(8).fill(0), Array(8).fill(0)];
+    for (let rank = 0; rank < 8; rank++) {
+      for (let file = 0; file < 8; file++) {
+        const piece = this.board[rank][file];
+        if (piece && piece.type === PieceType.Pawn) counts[piece.color][file]++;
+      }
+    }
+    return counts;
+  }
+
+  /**
+   * Check if the position has insufficient material for either side to checkmate.
+   * Only returns true when checkmate is literally impossible:
+   * - K vs K
+   * - K+B vs K
+   * - K+N vs K
+   * - K+B vs K+B (same color bishops)
+   */
+  public isInsufficientMaterial(): boolean {
+    const pieces: {
+      color: Color;
+      type: PieceType;
+      file: number;
+      rank: number;
+    }[] = [];
+
+    for (let rank = 0; rank < 8; rank++) {
+      for (let file = 0; file < 8; file++) {
+        const piece = this.board[rank][file];
+        if (piece) {
+          pieces.push({ ...piece, file, rank });
+        }
+      }
+    }
+
+    // Any pawns, rooks, or queens → sufficient material
+    if (
+      pieces.some(
+        p =>
+          p.type === PieceType.Pawn ||
+          p.type === PieceType.Rook ||
+          p.type === PieceType.Queen
+      )
+    ) {
+      return false;
+    }
+
+    // Filter to non-king pieces
+    const minors = pieces.filter(p => p.type !== PieceType.King);
+
+    // K vs K
+    if (minors.length === 0) return true;
+
+    // K+minor vs K
+    if (minors.length === 1) return true;
+
+    // K+B vs K+B — only insufficient if bishops are on the same color square
+    if (
+      minors.length === 2 &&
+      minors[0].type === PieceType.Bishop &&
+      minors[1].type === PieceType.Bishop &&
+      minors[0].color !== minors[1].color
+    ) {
+      const color0 = (minors[0].file + minors[0].rank) % 2;
+      const color1 = (minors[1].file + minors[1].rank) % 2;
+      if (color0 === color1) return true;
+    }
+
+    return false;
+  }
+
+  /**
+   * Whether `color` has enough material that it could ever mate: anything
+   * beyond a lone king, king and knight, or king and bishop. The opponent's
+   * pieces are not considered, so this is the test for whether a flag fall
+   * loses (the opponent has mating material) or draws.
+   */
+  public hasMatingMaterial(color: Color): boolean {
+    const sides = this.getMaterialSignature().split('v');
+    return !['K', 'KN', 'KB'].includes(sides[color]);
+  }
+
+  /**
+   * Whether this is the "wrong bishop" fortress: king, bishop and pawns all
+   * on one rook file against a bare king, where the bishop cannot control
+   * the promotion square and the defending king can reach that corner — it
+   * is already next to it, or no farther away (counting the move if it is
+   * its turn) than the front pawn is from promoting or the attacking king
+   * is from the corner. Such positions are drawn despite the extra material.
+   */
+  public isWrongBishopDraw(): boolean {
+    // Signature sides are indexed by color, White first
+    const sides = this.getMaterialSignature().split('v');
+    const strong = sides[Color.Black] === 'K' ? Color.White : Color.Black;
+    const defender = strong === Color.White ? Color.Black : Color.White;
+    if (sides[defender] !== 'K' || !/^KBP+$/.test(sides[strong])) return false;
+
+    const pieces = this.getPieces().filter(p => p.piece.color === strong);
+    const pawns = pieces.filter(p => p.piece.type === PieceType.Pawn);
+    const bishop = pieces.find(p => p.piece.type === PieceType.Bishop)!;
+    const file = pawns[0].position.file;
+    if (
+      (file !== 0 && file !== 7) ||
+      pawns.some(p => p.position.file !== file)
+    ) {
+      return false;
+    }
+
+    const promotion = { file, rank: strong === Color.White ? 7 : 0 };
+    const squareColor = (p: Position) => (p.file + p.rank) % 2;
+    if (squareColor(bishop.position) === squareColor(promotion)) return false;
+
+    const distance = (p: Position) =>
+      Math.max(
+        Math.abs(p.file - promotion.file),
+        Math.abs(p.rank - promotion.rank)
+      );
+    const defenderDistance =
+      distance(this.findKing(defender)!) -
+      (this.currentPlayer === defender ? 1 : 0);
+    if (defenderDistance <= 1) return true;
+
+    // Moves the front pawn needs to promote, using a double step if it can
+    const homeRank = strong === Color.White ? 1 : 6;
+    const pawnMoves = Math.min(
+      ...pawns.map(p => {
+        const moves = distance(p.position);
+        return p.position.rank === homeRank ? moves - 1 : moves;
+      })
+    );
+    return (
+      defenderDistance <= pawnMoves &&
+      defenderDistance <= distance(this.findKing(strong)!)
+    );
+  }
+
+  /**
+   * Parse a SAN (Standard Algebraic Notation) move and find the matching legal move.
+   * Returns the matching Move or null if no legal move matches.
+   */
+  public parseSAN(san: string): Move | null {
+    const FILE_CHARS = 'abcdefgh';
+    const cleaned = san.replace(/[+#!?]+$/, ''); // strip check/mate/annotation markers
+
+    // Castling
+    if (cleaned === 'O-O' || cleaned === '0-0') {
+      const rank = this.currentPlayer === Color.White ? 0 : 7;
+      return this.isValidMove({ file: 4, rank }, { file: 6, rank }).valid
+        ? { fromFile: 4, fromRank: rank, toFile: 6, toRank: rank }
+        : null;
+    }
+    if (cleaned === 'O-O-O' || cleaned === '0-0-0') {
+      const rank = this.currentPlayer === Color.White ? 0 : 7;
+      return this.isValidMove({ file: 4, rank }, { file: 2, rank }).valid
         ? { fromFile: 4, fromRank: rank, toFile: 2, toRank: rank }
         : null;
     }
@@ -1500,67 +3051,769 @@ export class ChessRules {
       rest = rest.slice(0, -2);
     }
 
-    // Remove capture indicator
-    rest = rest.replace('x', '');
+    // Remove capture indicator
+    rest = rest.replace('x', '');
+
+    // Determine piece type
+    let pieceType = PieceType.Pawn;
+    if (rest.length > 0 && 'KQRBN'.includes(rest[0])) {
+      const pieceMap: Record<string, PieceType> = {
+        K: PieceType.King,
+        Q: PieceType.Queen,
+        R: PieceType.Rook,
+        B: PieceType.Bishop,
+        N: PieceType.Knight,
+      };
+      pieceType = pieceMap[rest[0]];
+      rest = rest.slice(1);
+    }
+
+    // Last two characters are the destination square
+    if (rest.length < 2) return null;
+    const toFile = FILE_CHARS.indexOf(rest[rest.length - 2]);
+    const toRank = parseInt(rest[rest.length - 1]) - 1;
+    if (toFile < 0 || toRank < 0 || toRank > 7) return null;
+
+    // Disambiguation (remaining characters before destination)
+    const disambig = rest.slice(0, -2);
+    let disambigFile = -1;
+    let disambigRank = -1;
+    for (const ch of disambig) {
+      if (FILE_CHARS.includes(ch)) disambigFile = FILE_CHARS.indexOf(ch);
+      else if (ch >= '1' && ch <= '8') disambigRank = parseInt(ch) - 1;
+    }
+
+    // Find matching legal move
+    const to = { file: toFile, rank: toRank };
+    for (let rank = 0; rank < 8; rank++) {
+      for (let file = 0; file < 8; file++) {
+        const piece = this.board[rank][file];
+        if (
+          !piece ||
+          piece.color !== this.currentPlayer ||
+          piece.type !== pieceType
+        )
+          continue;
+        if (disambigFile >= 0 && file !== disambigFile) continue;
+        if (disambigRank >= 0 && rank !== disambigRank) continue;
+
+        const from = { file, rank };
+        const validation = this.isValidMove(from, to, promotionPiece);
+        if (validation.valid) {
+          return {
+            fromFile: file,
+            fromRank: rank,
+            toFile: toFile,
+            toRank: toRank,
+            promotionPiece,
+          };
+        }
+      }
+    }
+
+    return null;
+  }
+
+  /**
+   * Parse a UCI long-algebraic move ("e2e4", "e7e8q"). Only the format is
+   * checked; legality is left to makeMove().
+   */
+  private parseUCI(uci: string): Move | null {
+    const match = uci.match(/^([a-h])([1-8])([a-h])([1-8])([qrbn])?$/);
+    if (!match) return null;
+    const promoMap: Record<string, PieceType> = {
+      q: PieceType.Queen,
+      r: PieceType.Rook,
+      b: PieceType.Bishop,
+      n: PieceType.Knight,
+    };
+    return {
+      fromFile: match[1].charCodeAt(0) - 97,
+      fromRank: parseInt(match[2]) - 1,
+      toFile: match[3].charCodeAt(0) - 97,
+      toRank: parseInt(match[4]) - 1,
+      promotionPiece: match[5] ? promoMap[match[5]] : undefined,
+    };
+  }
+
+  /** Render a move in UCI long algebraic form, e.g. "e2e4" or "e7e8q". */
+  private formatUCI(move: Move): string {
+    const square = (file: number, rank: number) =>
+      String.fromCharCode(97 + file) + (rank + 1);
+    const promotion =
+      move.promotionPiece === undefined
+        ? ''
+        : this.pieceToFENChar({
+            type: move.promotionPiece,
+            color: Color.Black,
+          });
+    return (
+      square(move.fromFile, move.fromRank) +
+      square(move.toFile, move.toRank) +
+      promotion
+    );
+  }
+
+  /**
+   * Play a sequence of UCI moves ("e2e4", "e7e8q") in one call, e.g. to
+   * replay a game for a scrubber. Stops at the first malformed or illegal
+   * move, leaving the position after the last move that was applied.
+   * Returns the number of moves applied; if it is less than moves.length,
+   * moves[returned] is the one that was rejected.
+   */
+  public applyMoves(moves: string[]): number {
+    let applied = 0;
+    for (const uci of moves) {
+      const move = this.parseUCI(uci);
+      if (!move) break;
+      const result = this.makeMove(
+        { file: move.fromFile, rank: move.fromRank },
+        { file: move.toFile, rank: move.toRank },
+        move.promotionPiece
+      );
+      if (!result.success) break;
+      applied++;
+    }
+    return applied;
+  }
+
+  /**
+   * Set up a position from a UCI "position" command: "startpos moves e2e4
+   * e7e5" or "fen <FEN> moves ...", with or without the leading "position"
+   * and "moves" part. Returns null on success, otherwise a message naming
+   * the rejected token, in which case the engine is left unchanged.
+   */
+  public setPositionUCI(command: string): string | null {
+    const tokens = command.trim().split(/\s+/);
+    if (tokens[0] === 'position') tokens.shift();
+    const movesAt = tokens.indexOf('moves');
+    const setup = movesAt === -1 ? tokens : tokens.slice(0, movesAt);
+    const moves = movesAt === -1 ? [] : tokens.slice(movesAt + 1);
+
+    let fen: string;
+    if (setup[0] === 'startpos') {
+      if (setup.length > 1) return `unexpected token '${setup[1]}'`;
+      fen = STARTING_FEN;
+    } else if (setup[0] === 'fen') {
+      fen = setup.slice(1).join(' ');
+    } else {
+      return `expected 'startpos' or 'fen', got '${setup[0] ?? ''}'`;
+    }
+
+    // Dry run on a scratch engine so a bad move leaves this one untouched
+    const probe = new ChessRules();
+    const fenError = probe.validateFEN(fen);
+    if (fenError !== null) return `invalid FEN '${fen}': ${fenError}`;
+    probe.setPosition(fen);
+    const applied = probe.applyMoves(moves);
+    if (applied < moves.length) {
+      return `illegal or malformed move '${moves[applied]}'`;
+    }
+
+    this.setPosition(fen);
+    this.applyMoves(moves);
+    return null;
+  }
+
+  /**
+   * Export the game as PGN. The Seven Tag Roster (Event, Site, Date, Round,
+   * White, Black, Result) is always emitted, with missing tags set to "?";
+   * any other supplied tags follow. The Result tag and the movetext's
+   * termination reflect `result`, by default the engine's own result; pass
+   * one to record an outcome the engine does not track, such as an agreed
+   * draw or a loss on time. Games that did not start from the initial
+   * position get SetUp/FEN tags.
+   */
+  public toPGN(
+    headers: Record<string, string> = {},
+    gameResult: GameResult | null = this.getGameResult()
+  ): string {
+    const result = ChessRules.getResultToken(gameResult);
+    const tags: Record<string, string> = {};
+    for (const name of ChessRules.SEVEN_TAG_ROSTER) {
+      tags[name] = headers[name] ?? '?';
+    }
+    for (const [name, value] of Object.entries(headers)) {
+      if (!(name in tags)) tags[name] = value;
+    }
+    tags.Result = result;
+    const startFen = this.fenHistory[0];
+    if (startFen !== STARTING_FEN) {
+      tags.SetUp = '1';
+      tags.FEN = startFen;
+    }
+
+    const tagLines = Object.entries(tags).map(
+      ([name, value]) =>
+        `[${name} "${value.replace(/\\/g, '\\\\').replace(/"/g, '\\"')}"]`
+    );
+
+    // Movetext, numbered from the starting position's fullmove counter
+    const [, activeColor, , , , fullmoveField] = startFen.split(' ');
+    let moveNumber = parseInt(fullmoveField);
+    let whiteToMove = activeColor === 'w';
+    const tokens: string[] = [];
+    let afterComment = false;
+    this.historyEntries.forEach((entry, index) => {
+      if (whiteToMove) {
+        tokens.push(`${moveNumber}.`);
+      } else if (index === 0 || afterComment) {
+        tokens.push(`${moveNumber}...`);
+      }
+      tokens.push(entry.algebraic);
+      for (const nag of entry.nags ?? []) tokens.push(`$${nag}`);
+      afterComment = !!entry.comment;
+      if (entry.comment) tokens.push(`{${entry.comment}}`);
+      if (!whiteToMove) moveNumber++;
+      whiteToMove = !whiteToMove;
+    });
+    tokens.push(result);
+
+    return `${tagLines.join('\n')}\n\n${this.wrapPGN(tokens)}\n`;
+  }
+
+  /**
+   * Load a game from PGN, the inverse of toPGN(): SAN moves with their
+   * {comments} and $NAGs, played from the FEN tag's position if there is one
+   * and the initial position otherwise. Other tags, move numbers, the result
+   * and variations are skipped. Returns false, leaving the engine untouched,
+   * if the FEN or any move is invalid.
+   */
+  public loadPGN(pgn: string): boolean {
+    let fen = STARTING_FEN;
+    const movetext: string[] = [];
+    for (const line of pgn.split('\n').map(l => l.trim())) {
+      if (line.startsWith('[')) {
+        const tag = /^\[FEN "((?:[^"\\]|\\.)*)"\]$/.exec(line);
+        if (tag) fen = tag[1].replace(/\\(.)/g, '$1');
+      } else if (line) {
+        movetext.push(line);
+      }
+    }
+    const results = ['1-0', '0-1', '1/2-1/2', '*'];
+    const tokens = (
+      movetext
+        .join(' ')
+        .replace(/\([^)]*\)/g, '') // variations
+        .match(/\{[^}]*\}|[^\s{]+/g) ?? []
+    ).filter(token => !/^\d+\.+$/.test(token) && !results.includes(token));
+
+    const replay = (engine: ChessRules) => {
+      if (!engine.setPosition(fen)) return false;
+      for (const token of tokens) {
+        // Comments and NAGs annotate the move just played
+        const ply = engine.historyEntries.length - 1;
+        if (token.startsWith('{')) {
+          engine.setMoveComment(ply, token.slice(1, -1));
+        } else if (/^\$\d+$/.test(token)) {
+          const nags = engine.historyEntries[ply]?.nags ?? [];
+          engine.setMoveNags(ply, [...nags, parseInt(token.slice(1))]);
+        } else {
+          const move = engine.parseSAN(token);
+          if (!move) return false;
+          const result = engine.makeMove(
+            { file: move.fromFile, rank: move.fromRank },
+            { file: move.toFile, rank: move.toRank },
+            move.promotionPiece
+          );
+          if (!result.success) return false;
+        }
+      }
+      return true;
+    };
+    // Dry run on a scratch engine so a bad game leaves this one untouched
+    if (!replay(new ChessRules())) return false;
+    replay(this);
+    return true;
+  }
+
+  /**
+   * Attach a comment to the move at `ply` (0-based index into getHistory()),
+   * exported by toPGN() as `{comment}` after the move. An empty comment
+   * removes it. Returns false if there is no such move.
+   */
+  public setMoveComment(ply: number, comment: string): boolean {
+    const entry = this.historyEntries[ply];
+    if (!entry) return false;
+    // Braces would end the PGN comment early
+    const cleaned = comment.replace(/[{}]/g, '').trim();
+    this.historyEntries[ply] = { ...entry, comment: cleaned || undefined };
+    return true;
+  }
+
+  /**
+   * Set the numeric annotation glyphs of the move at `ply` (e.g. [1] for
+   * "!", [4] for "??"), exported by toPGN() as `$1`, `$4`. An empty list
+   * removes them. Returns false if there is no such move or a NAG is not an
+   * integer from 0 to 255.
+   */
+  public setMoveNags(ply: number, nags: number[]): boolean {
+    const entry = this.historyEntries[ply];
+    if (!entry) return false;
+    if (nags.some(nag => !Number.isInteger(nag) || nag < 0 || nag > 255)) {
+      return false;
+    }
+    this.historyEntries[ply] = {
+      ...entry,
+      nags: nags.length > 0 ? [...nags] : undefined,
+    };
+    return true;
+  }
+
+  private static readonly SEVEN_TAG_ROSTER = [
+    'Event',
+    'Site',
+    'Date',
+    'Round',
+    'White',
+    'Black',
+    'Result',
+  ];
+
+  /** PGN result token for a game result: 1-0, 0-1, 1/2-1/2 or *. */
+  private static getResultToken(result: GameResult | null): string {
+    if (!result) return '*';
+    if (result.winner === Color.White) return '1-0';
+    if (result.winner === Color.Black) return '0-1';
+    return '1/2-1/2';
+  }
+
+  /** Join movetext tokens into lines of at most 80 characters. */
+  private wrapPGN(tokens: string[]): string {
+    const lines: string[] = [];
+    let line = '';
+    for (const token of tokens) {
+      if (line && line.length + 1 + token.length > 80) {
+        lines.push(line);
+        line = token;
+      } else {
+        line = line ? `${line} ${token}` : token;
+      }
+    }
+    lines.push(line);
+    return lines.join('\n');
+  }
+
+  /**
+   * Name of the opening played, from a small built-in table keyed by SAN
+   * move prefix. The longest matching line wins, so 1. e4 e5 2. Nf3 Nc6
+   * 3. Bc4 Bc5 is the Giuoco Piano rather than the Italian Game. Returns null
+   * when the first moves are in no line of the table, or the game did not
+   * start from the initial position.
+   */
+  public getOpeningName(): string | null {
+    if (this.fenHistory[0] !== STARTING_FEN) return null;
+    const played = this.historyEntries.map(entry =>
+      entry.algebraic.replace(/[+#]$/, '')
+    );
+    let best: { name: string; length: number } | null = null;
+    for (const [line, name] of ChessRules.OPENINGS) {
+      const moves = line.split(' ');
+      if (
+        moves.length <= played.length &&
+        moves.every((san, i) => played[i] === san) &&
+        (!best || moves.length > best.length)
+      ) {
+        best = { name, length: moves.length };
+      }
+    }
+    return best?.name ?? null;
+  }
+
+  private static readonly OPENINGS: ReadonlyArray<[string, string]> = [
+    ['e4', "King's Pawn Opening"],
+    ['e4 e5', 'Open Game'],
+    ['e4 e5 Nf3 Nc6 Bb5', 'Ruy Lopez'],
+    ['e4 e5 Nf3 Nc6 Bc4', 'Italian Game'],
+    ['e4 e5 Nf3 Nc6 Bc4 Bc5', 'Giuoco Piano'],
+    ['e4 e5 Nf3 Nc6 Bc4 Nf6', 'Two Knights Defense'],
+    ['e4 e5 Nf3 Nc6 d4', 'Scotch Game'],
+    ['e4 e5 Nf3 Nc6 Nc3 Nf6', 'Four Knights Game'],
+    ['e4 e5 Nf3 Nf6', "Petrov's Defense"],
+    ['e4 e5 Nf3 d6', 'Philidor Defense'],
+    ['e4 e5 f4', "King's Gambit"],
+    ['e4 e5 Nc3', 'Vienna Game'],
+    ['e4 c5', 'Sicilian Defense'],
+    ['e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6', 'Sicilian Defense, Najdorf'],
+    ['e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 g6', 'Sicilian Defense, Dragon'],
+    ['e4 e6', 'French Defense'],
+    ['e4 c6', 'Caro-Kann Defense'],
+    ['e4 d5', 'Scandinavian Defense'],
+    ['e4 d6 d4 Nf6 Nc3 g6', 'Pirc Defense'],
+    ['e4 g6', 'Modern Defense'],
+    ['e4 Nf6', "Alekhine's Defense"],
+    ['d4', "Queen's Pawn Opening"],
+    ['d4 d5 c4', "Queen's Gambit"],
+    ['d4 d5 c4 dxc4', "Queen's Gambit Accepted"],
+    ['d4 d5 c4 e6', "Queen's Gambit Declined"],
+    ['d4 d5 c4 c6', 'Slav Defense'],
+    ['d4 d5 Bf4', 'London System'],
+    ['d4 Nf6 c4 g6', "King's Indian Defense"],
+    ['d4 Nf6 c4 g6 Nc3 d5', 'Grünfeld Defense'],
+    ['d4 Nf6 c4 e6 Nc3 Bb4', 'Nimzo-Indian Defense'],
+    ['d4 Nf6 c4 e6 Nf3 b6', "Queen's Indian Defense"],
+    ['d4 f5', 'Dutch Defense'],
+    ['c4', 'English Opening'],
+    ['Nf3 d5 c4', 'Réti Opening'],
+  ];
+
+  /**
+   * Render a move in SAN for the current position without playing it.
+   * Returns null if the move is not legal here.
+   */
+  public moveToSAN(move: Move): string | null {
+    const probe = new ChessRules();
+    probe.setPosition(this.generateFEN());
+    const result = probe.makeMove(
+      { file: move.fromFile, rank: move.fromRank },
+      { file: move.toFile, rank: move.toRank },
+      move.promotionPiece
+    );
+    return result.success ? probe.historyEntries[0].algebraic : null;
+  }
+
+  /**
+   * Every legal move in SAN, in the same order as generateLegalMoves() —
+   * e.g. for a move-suggestion dropdown. Disambiguation and check/mate
+   * suffixes are computed exactly as for moves that are played.
+   */
+  public getLegalMovesSAN(): string[] {
+    const probe = this.clone();
+    const sans: string[] = [];
+    for (const move of this.generateLegalMoves()) {
+      const result = probe.makeMove(
+        { file: move.fromFile, rank: move.fromRank },
+        { file: move.toFile, rank: move.toRank },
+        move.promotionPiece
+      );
+      if (!result.success) continue;
+      sans.push(
+        probe.historyEntries[probe.historyEntries.length - 1].algebraic
+      );
+      probe.undoMove();
+    }
+    return sans;
+  }
+
+  /**
+   * SAN prefixed with the move number, e.g. "15. e4" for White or
+   * "15... Nf6" for Black. Returns null if the move is not legal here.
+   */
+  public moveToNumberedSAN(move: Move): string | null {
+    const san = this.moveToSAN(move);
+    if (san === null) return null;
+    const separator = this.currentPlayer === Color.White ? '.' : '...';
+    return `${this.fullmoveNumber}${separator} ${san}`;
+  }
+
+  /**
+   * Whether playing `move` would leave the opponent stalemated, so a coach
+   * UI can flag the blunder before it is made. Returns null if the move is
+   * not legal here.
+   */
+  public moveCausesStalemate(move: Move): boolean | null {
+    const probe = this.clone();
+    const result = probe.makeMove(
+      { file: move.fromFile, rank: move.fromRank },
+      { file: move.toFile, rank: move.toRank },
+      move.promotionPiece
+    );
+    if (!result.success) return null;
+    return (
+      probe.getLegalMoveCount() === 0 &&
+      !probe.isKingInCheck(probe.currentPlayer)
+    );
+  }
+
+  /**
+   * Legal destinations of the piece on `from` that give check — for "find
+   * the checking move" hints. A promotion square counts if any promotion
+   * piece checks from it. Empty when the piece has no checking moves or
+   * does not belong to the side to move.
+   */
+  public getCheckingDestinations(from: Position): Position[] {
+    const destinations = this.getValidMoves(from);
+    if (destinations.length === 0) return [];
+
+    const probe = this.clone();
+    return destinations.filter(to => {
+      const promotions = this.isPromotionMove(from, to)
+        ? [PieceType.Queen, PieceType.Rook, PieceType.Bishop, PieceType.Knight]
+        : [undefined];
+      return promotions.some(promotionPiece => {
+        if (!probe.makeMove(from, to, promotionPiece).success) return false;
+        const check = probe.isKingInCheck(probe.currentPlayer);
+        probe.undoMove();
+        return check;
+      });
+    });
+  }
+
+  /**
+   * Pick a legal move pseudo-randomly but reproducibly: the same position
+   * and seed always yield the same move. Returns null if there are no legal
+   * moves. Handy for weak bots and for fuzzing the rules with random games.
+   */
+  public randomLegalMove(seed: number): Move | null {
+    const moves = this.getGameState().validMoves;
+    if (moves.length === 0) return null;
+
+    // mulberry32: a tiny, well-distributed 32-bit PRNG
+    let t = (seed + 0x6d2b79f5) | 0;
+    t = Math.imul(t ^ (t >>> 15), t | 1);
+    t ^= t + Math.imul(t ^ (t >>> 7), t | 61);
+    const random = ((t ^ (t >>> 14)) >>> 0) / 4294967296;
+
+    return { ...moves[Math.floor(random * moves.length)] };
+  }
+
+  /**
+   * The material on the board as White's pieces, "v", then Black's, each
+   * side strongest first with the letters K, Q, R, B, N, P — e.g. "KQvK" or
+   * "KRPvKR". Endgame tables and statistics key on this.
+   */
+  public getMaterialSignature(): string {
+    const letters: Record<PieceType, string> = {
+      [PieceType.King]: 'K',
+      [PieceType.Queen]: 'Q',
+      [PieceType.Rook]: 'R',
+      [PieceType.Bishop]: 'B',
+      [PieceType.Knight]: 'N',
+      [PieceType.Pawn]: 'P',
+    };
+    const order = 'KQRBNP';
+    const sides = ['', ''];
+    for (const row of this.board) {
+      for (const piece of row) {
+        if (piece) sides[piece.color] += letters[piece.type];
+      }
+    }
+    const sorted = (side: string) =>
+      [...side].sort((a, b) => order.indexOf(a) - order.indexOf(b)).join('');
+    return `${sorted(sides[Color.White])}v${sorted(sides[Color.Black])}`;
+  }
+
+  /**
+   * Play K+Q vs K or K+R vs K perfectly: returns the move that mates
+   * fastest, read from a small distance-to-mate table built by retrograde
+   * analysis on first use (so it never stalemates or drops the heavy
+   * piece). Returns null unless the side to move has exactly king and queen
+   * or king and rook against a bare king, or if the position cannot be won.
+   */
+  public findBasicEndgameMove(): Move | null {
+    const strong = this.currentPlayer;
+    const sides = this.getMaterialSignature().split('v');
+    const [ours, theirs] = strong === Color.White ? sides : sides.reverse();
+    if (theirs !== 'K' || (ours !== 'KQ' && ours !== 'KR')) return null;
+    const heavyType = ours === 'KQ' ? PieceType.Queen : PieceType.Rook;
+
+    const table = ChessRules.getBasicEndgameTable(heavyType);
+    const weak = strong === Color.White ? Color.Black : Color.White;
+    const square = (p: Position) => p.rank * 8 + p.file;
+    let best: Move | null = null;
+    let bestDistance = Infinity;
+    let bestReplies = Infinity;
+    for (const move of this.generateLegalMoves()) {
+      const probe = this.clone();
+      probe.makeMove(
+        { file: move.fromFile, rank: move.fromRank },
+        { file: move.toFile, rank: move.toRank }
+      );
+      const heavyAfter = probe
+        .getPieces()
+        .find(({ piece }) => piece.type === heavyType);
+      if (!heavyAfter) continue;
+      // The table never rates a stalemate as a win, but check explicitly:
+      // stalemating the lone king throws the win away
+      const replies = probe.getLegalMoveCount();
+      if (replies === 0 && !probe.isKingInCheck(weak)) continue;
+      const s = square(probe.findKing(strong)!);
+      const h = square(heavyAfter.position);
+      const w = square(probe.findKing(weak)!);
+      const distance = table[(s * 64 + h) * 64 + w];
+      // Equally fast mates: prefer the one leaving the king fewest moves
+      if (
+        distance >= 0 &&
+        (distance < bestDistance ||
+          (distance === bestDistance && replies < bestReplies))
+      ) {
+        bestDistance = distance;
+        bestReplies = replies;
+        best = move;
+      }
+    }
+    return best;
+  }
+
+  private static basicEndgameTables: Partial<Record<PieceType, Int8Array>> =
+    {};
 
-    // Determine piece type
-    let pieceType = PieceType.Pawn;
-    if (rest.length > 0 && 'KQRBN'.includes(rest[0])) {
-      const pieceMap: Record<string, PieceType> = {
-        K: PieceType.King,
-        Q: PieceType.Queen,
-        R: PieceType.Rook,
-        B: PieceType.Bishop,
-        N: PieceType.Knight,
-      };
-      pieceType = pieceMap[rest[0]];
-      rest = rest.slice(1);
+  /**
+   * Distance to mate, in plies, for every K+Q/K+R vs K position with the lone
+   * king to move, indexed by (strongKing * 64 + heavy) * 64 + weakKing with
+   * squares numbered rank * 8 + file. -1 marks illegal or drawn positions.
+   * Built backwards from the mates: a position with the lone king to move is
+   * lost once every reply runs into a position where the strong side can
+   * move into an already-lost one.
+   */
+  private static getBasicEndgameTable(heavyType: PieceType): Int8Array {
+    const cached = ChessRules.basicEndgameTables[heavyType];
+    if (cached) return cached;
+
+    const isQueen = heavyType === PieceType.Queen;
+    const directions = isQueen
+      ? [...ChessRules.ROOK_DIRECTIONS, ...ChessRules.BISHOP_DIRECTIONS]
+      : ChessRules.ROOK_DIRECTIONS;
+    const fileOf = (sq: number) => sq & 7;
+    const rankOf = (sq: number) => sq >> 3;
+    const index = (s: number, h: number, w: number) => (s * 64 + h) * 64 + w;
+    const adjacent = (a: number, b: number) =>
+      Math.max(
+        Math.abs(fileOf(a) - fileOf(b)),
+        Math.abs(rankOf(a) - rankOf(b))
+      ) === 1;
+    const kingSteps = Array.from({ length: 64 }, (_, sq) =>
+      ChessRules.KING_OFFSETS.map(([df, dr]) => [
+        fileOf(sq) + df,
+        rankOf(sq) + dr,
+      ])
+        .filter(([f, r]) => f >= 0 && f < 8 && r >= 0 && r < 8)
+        .map(([f, r]) => r * 8 + f)
+    );
+    const neighbours = (sq: number) => kingSteps[sq];
+    // Squares the heavy piece on `h` slides over, stopping at any blocker
+    const slides = (h: number, ...blockers: number[]) => {
+      const squares: number[] = [];
+      for (const [df, dr] of directions) {
+        let f = fileOf(h) + df;
+        let r = rankOf(h) + dr;
+        while (f >= 0 && f < 8 && r >= 0 && r < 8) {
+          if (blockers.includes(r * 8 + f)) break;
+          squares.push(r * 8 + f);
+          f += df;
+          r += dr;
+        }
+      }
+      return squares;
+    };
+    // Whether the heavy piece on `h` attacks `t` past the strong king on `s`
+    const hits = (h: number, t: number, s: number) => {
+      const df = fileOf(t) - fileOf(h);
+      const dr = rankOf(t) - rankOf(h);
+      if (h === t) return false;
+      if (df !== 0 && dr !== 0 && (!isQueen || Math.abs(df) !== Math.abs(dr))) {
+        return false;
+      }
+      const step = Math.sign(dr) * 8 + Math.sign(df);
+      for (let sq = h + step; sq !== t; sq += step) {
+        if (sq === s) return false;
+      }
+      return true;
+    };
+
+    const size = 64 * 64 * 64;
+    const distance = new Int8Array(size).fill(-1);
+    const remaining = new Int8Array(size); // Unrefuted lone-king replies
+    const won = new Uint8Array(size); // Strong side to move and winning
+    const queue: number[] = [];
+
+    // Count the lone king's replies in every legal position; mates seed the
+    // queue. A reply that takes an unprotected heavy piece escapes forever.
+    for (let s = 0; s < 64; s++) {
+      for (let h = 0; h < 64; h++) {
+        if (h === s) continue;
+        for (let w = 0; w < 64; w++) {
+          if (w === s || w === h || adjacent(w, s)) continue;
+          let replies = 0;
+          for (const t of neighbours(w)) {
+            if (adjacent(t, s)) continue;
+            if (t === h) {
+              replies = 100; // Captures the heavy piece: a draw
+              break;
+            }
+            if (!hits(h, t, s)) replies++;
+          }
+          const idx = index(s, h, w);
+          remaining[idx] = replies;
+          if (replies === 0 && hits(h, w, s)) {
+            distance[idx] = 0;
+            queue.push(idx);
+          }
+        }
+      }
     }
 
-    // Last two characters are the destination square
-    if (rest.length < 2) return null;
-    const toFile = FILE_CHARS.indexOf(rest[rest.length - 2]);
-    const toRank = parseInt(rest[rest.length - 1]) - 1;
-    if (toFile < 0 || toRank < 0 || toRank > 7) return null;
+    for (let head = 0; head < queue.length; head++) {
+      const idx = queue[head];
+      const w = idx & 63;
+      const h = (idx >> 6) & 63;
+      const s = idx >> 12;
+
+      // Strong-side moves that led here: the king or the heavy piece
+      // stepped back from a square where the lone king was not in check
+      const before: number[] = [];
+      for (const from of neighbours(s)) {
+        if (from !== h && from !== w && !adjacent(from, w)) {
+          if (!hits(h, w, from)) before.push(index(from, h, w));
+        }
+      }
+      for (const from of slides(h, s, w)) {
+        if (!hits(from, w, s)) before.push(index(s, from, w));
+      }
 
-    // Disambiguation (remaining characters before destination)
-    const disambig = rest.slice(0, -2);
-    let disambigFile = -1;
-    let disambigRank = -1;
-    for (const ch of disambig) {
-      if (FILE_CHARS.includes(ch)) disambigFile = FILE_CHARS.indexOf(ch);
-      else if (ch >= '1' && ch <= '8') disambigRank = parseInt(ch) - 1;
+      for (const winning of before) {
+        if (won[winning]) continue;
+        won[winning] = 1;
+        const ws = winning >> 12;
+        const wh = (winning >> 6) & 63;
+        // Lone-king moves into this winning position lose one more option
+        for (const from of neighbours(w)) {
+          if (from === ws || from === wh || adjacent(from, ws)) continue;
+          const previous = index(ws, wh, from);
+          if (--remaining[previous] === 0) {
+            distance[previous] = distance[idx] + 2;
+            queue.push(previous);
+          }
+        }
+      }
     }
 
-    // Find matching legal move
-    const to = { file: toFile, rank: toRank };
+    ChessRules.basicEndgameTables[heavyType] = distance;
+    return distance;
+  }
+
+  /**
+   * True if the side to move has at least one legal capture, including en
+   * passant and capturing promotions. Stops at the first one found.
+   */
+  public hasCapture(): boolean {
     for (let rank = 0; rank < 8; rank++) {
       for (let file = 0; file < 8; file++) {
         const piece = this.board[rank][file];
-        if (
-          !piece ||
-          piece.color !== this.currentPlayer ||
-          piece.type !== pieceType
-        )
-          continue;
-        if (disambigFile >= 0 && file !== disambigFile) continue;
-        if (disambigRank >= 0 && rank !== disambigRank) continue;
-
+        if (!piece || piece.color !== this.currentPlayer) continue;
         const from = { file, rank };
-        const validation = this.isValidMove(from, to, promotionPiece);
-        if (validation.valid) {
-          return {
-            fromFile: file,
-            fromRank: rank,
-            toFile: toFile,
-            toRank: toRank,
-            promotionPiece,
-          };
+        for (const to of this.getValidMovesForPiece(file, rank, piece)) {
+          const isCapture =
+            this.board[to.rank][to.file] !== null ||
+            (piece.type === PieceType.Pawn &&
+              this.enPassantTarget !== null &&
+              to.file === this.enPassantTarget.file &&
+              to.rank === this.enPassantTarget.rank);
+          if (isCapture && this.isMoveLegal(from, to)) return true;
         }
       }
     }
+    return false;
+  }
 
-    return null;
+  /** True if the side to move is in check and has no legal move. */
+  public isCheckmate(): boolean {
+    return this.isKingInCheck(this.currentPlayer) && !this.hasAnyLegalMove();
+  }
+
+  /** True if the side to move is not in check but has no legal move. */
+  public isStalemate(): boolean {
+    return !this.isKingInCheck(this.currentPlayer) && !this.hasAnyLegalMove();
   }
 
   /** True if the current player has at least one legal move (cheap mate/stalemate probe). */
@@ -1576,19 +3829,22 @@ export class ChessRules {
     return false;
   }
 
-  public getGameState(): GameState {
-    if (this.cachedGameState) return this.cachedGameState;
-
-    // Get all valid moves for current player. getValidMoves already returns
-    // fully legal destinations, so no re-validation is needed — a pawn move
-    // to the last rank is a promotion by definition and expands to all four
-    // promotion choices.
-    const validMoves: Move[] = [];
+  /**
+   * Append every legal move for the side to move to `out` and return it.
+   * getValidMoves already returns fully legal destinations, so no
+   * re-validation is needed — a pawn move to the last rank is a promotion by
+   * definition and expands to all four promotion choices. Callers generating
+   * many positions (perft, analysis) can pass one cleared array each time
+   * instead of allocating a fresh list per node.
+   */
+  public generateLegalMoves(out: Move[] = []): Move[] {
+    const pins = this.getPinsIfNotInCheck();
     for (let rank = 0; rank < 8; rank++) {
       for (let file = 0; file < 8; file++) {
         const piece = this.board[rank][file];
         if (piece && piece.color === this.currentPlayer) {
-          const pieceMoves = this.getValidMoves({ file, rank });
+          const from = { file, rank };
+          const pieceMoves = this.getLegalDestinations(from, piece, pins);
           const promotionRank =
             piece.type === PieceType.Pawn
               ? piece.color === Color.White
@@ -1603,7 +3859,7 @@ export class ChessRules {
                 PieceType.Bishop,
                 PieceType.Knight,
               ]) {
-                validMoves.push({
+                out.push({
                   fromFile: file,
                   fromRank: rank,
                   toFile: move.file,
@@ -1612,7 +3868,7 @@ export class ChessRules {
                 });
               }
             } else {
-              validMoves.push({
+              out.push({
                 fromFile: file,
                 fromRank: rank,
                 toFile: move.file,
@@ -1623,9 +3879,209 @@ export class ChessRules {
         }
       }
     }
+    return out;
+  }
+
+  /**
+   * Legal moves for `color` as if it were its turn, in the same position —
+   * e.g. "what could Black do if it were Black's move". Moves are checked
+   * against `color`'s own king. An en passant capture is only available to
+   * the side to move, so the other side never gets one.
+   */
+  public getLegalMovesForColor(color: Color): Move[] {
+    if (color === this.currentPlayer) return this.generateLegalMoves();
+
+    const { currentPlayer, enPassantTarget } = this;
+    this.currentPlayer = color;
+    this.enPassantTarget = null;
+    try {
+      return this.generateLegalMoves();
+    } finally {
+      this.currentPlayer = currentPlayer;
+      this.enPassantTarget = enPassantTarget;
+    }
+  }
+
+  /**
+   * Static exchange evaluation: the material `move` wins (positive) or loses
+   * (negative) in centipawns if both sides then keep recapturing on its
+   * destination with their cheapest legal piece, each stopping when that
+   * would lose material. Returns null if the move is not legal here.
+   */
+  public staticExchange(move: Move): number | null {
+    const probe = this.clone();
+    const result = probe.makeMove(
+      { file: move.fromFile, rank: move.fromRank },
+      { file: move.toFile, rank: move.toRank },
+      move.promotionPiece
+    );
+    if (!result.success) return null;
+    const captured = result.capturedPiece
+      ? this.pieceValues[result.capturedPiece.type]
+      : 0;
+    const target = { file: move.toFile, rank: move.toRank };
+    return captured - probe.recaptureGain(target);
+  }
+
+  /** Best material the side to move can win by recapturing on `target`. */
+  private recaptureGain(target: Position): number {
+    const victim = this.getPiece(target);
+    if (!victim) return 0;
+    let cheapest: Move | null = null;
+    let cheapestValue = Infinity;
+    for (const move of this.generateLegalMoves()) {
+      if (move.toFile !== target.file || move.toRank !== target.rank) {
+        continue;
+      }
+      const attacker = this.board[move.fromRank][move.fromFile]!;
+      const value = this.pieceValues[attacker.type];
+      if (
+        value < cheapestValue ||
+        (value === cheapestValue && move.promotionPiece === PieceType.Queen)
+      ) {
+        cheapest = move;
+        cheapestValue = value;
+      }
+    }
+    if (!cheapest) return 0;
+
+    this.makeMove(
+      { file: cheapest.fromFile, rank: cheapest.fromRank },
+      { file: cheapest.toFile, rank: cheapest.toRank },
+      cheapest.promotionPiece
+    );
+    const gain = this.pieceValues[victim.type] - this.recaptureGain(target);
+    this.undoMove();
+    return Math.max(0, gain);
+  }
+
+  /**
+   * Legal captures that win material by static exchange evaluation — a
+   * hanging piece, or one defended too weakly. The "you can win material
+   * here" hint; empty when no capture comes out ahead.
+   */
+  public getFreeCaptures(): Move[] {
+    return this.generateLegalMoves().filter(
+      move =>
+        this.classifyMove(move)!.isCapture && this.staticExchange(move)! > 0
+    );
+  }
+
+  /** Every legal move that checkmates at once; empty if there is none. */
+  public getMatesInOne(): Move[] {
+    const probe = this.clone();
+    return this.generateLegalMoves().filter(move => {
+      const result = probe.makeMove(
+        { file: move.fromFile, rank: move.fromRank },
+        { file: move.toFile, rank: move.toRank },
+        move.promotionPiece
+      );
+      // Undoing a move that was not made would pop the wrong snapshot
+      if (!result.success) return false;
+      const mate =
+        probe.isKingInCheck(probe.currentPlayer) && !probe.hasAnyLegalMove();
+      probe.undoMove();
+      return mate;
+    });
+  }
+
+  /**
+   * Whether the opponent would have a mate in one if it were their move —
+   * found by passing the turn with a null move — so the UI can warn before
+   * the side to move overlooks it. False while the side to move is in check,
+   * since the turn cannot be passed then.
+   */
+  public opponentThreatensMate(): boolean {
+    const probe = this.clone();
+    if (!probe.makeNullMove()) return false;
+    return probe.getMatesInOne().length > 0;
+  }
+
+  /**
+   * Legal moves that are neither captures (including en passant),
+   * promotions nor checks — the quiet moves a move orderer tries last.
+   */
+  public getQuietMoves(): Move[] {
+    const probe = this.clone();
+    return this.generateLegalMoves().filter(move => {
+      if (move.promotionPiece !== undefined) return false;
+      if (this.classifyMove(move)!.isCapture) return false;
+      const result = probe.makeMove(
+        { file: move.fromFile, rank: move.fromRank },
+        { file: move.toFile, rank: move.toRank }
+      );
+      if (!result.success) return false;
+      const check = probe.isKingInCheck(probe.currentPlayer);
+      probe.undoMove();
+      return !check;
+    });
+  }
+
+  /**
+   * Diagnostic for fuzzing the rules: checks that every move from
+   * generateLegalMoves() is accepted by makeMove(), and that makeMove()
+   * accepts no move the generator missed. Returns null when both agree,
+   * otherwise a description of the first mismatch.
+   */
+  public verifyMoveConsistency(): string | null {
+    const generated = this.generateLegalMoves();
+    const probe = this.clone();
+    for (const move of generated) {
+      const result = probe.makeMove(
+        { file: move.fromFile, rank: move.fromRank },
+        { file: move.toFile, rank: move.toRank },
+        move.promotionPiece
+      );
+      if (!result.success) {
+        const uci = this.formatUCI(move);
+        return `generated move ${uci} is rejected by makeMove()`;
+      }
+      probe.undoMove();
+    }
+
+    const known = new Set(generated.map(move => this.formatUCI(move)));
+    const allPromotions = [
+      PieceType.Queen,
+      PieceType.Rook,
+      PieceType.Bishop,
+      PieceType.Knight,
+    ];
+    for (const { piece, position: from } of this.getPieces()) {
+      if (piece.color !== this.currentPlayer) continue;
+      for (let rank = 0; rank < 8; rank++) {
+        for (let file = 0; file < 8; file++) {
+          const to = { file, rank };
+          const promotions = this.isPromotionMove(from, to)
+            ? allPromotions
+            : [undefined];
+          for (const promotionPiece of promotions) {
+            const uci = this.formatUCI({
+              fromFile: from.file,
+              fromRank: from.rank,
+              toFile: file,
+              toRank: rank,
+              promotionPiece,
+            });
+            if (
+              this.isValidMove(from, to, promotionPiece).valid &&
+              !known.has(uci)
+            ) {
+              return `move ${uci} is accepted by makeMove() but not generated`;
+            }
+          }
+        }
+      }
+    }
+    return null;
+  }
+
+  public getGameState(): GameState {
+    if (this.cachedGameState) return this.cachedGameState;
+
+    const validMoves = this.generateLegalMoves();
 
     // Calculate captured pieces
-    const capturedPieces = this.getCapturedPieces();
+    const capturedPieces = this.getMissingPieces();
 
     // Check game over conditions
     const isGameOver = validMoves.length === 0;
@@ -1661,7 +4117,40 @@ export class ChessRules {
     return this.cachedGameState;
   }
 
-  private getCapturedPieces(): { white: Piece[]; black: Piece[] } {
+  /**
+   * Record that `color` resigns. The opponent is reported as the winner by
+   * getGameResult() until the resignation is withdrawn: playing on with
+   * makeMove(), taking back a move with undoMove(), resetGame() and
+   * setPosition() all clear it. Returns false if the game is already over.
+   */
+  public resign(color: Color): boolean {
+    if (this.getGameResult()) return false;
+    this.resignedBy = color;
+    return true;
+  }
+
+  /**
+   * The outcome of the game, or null while it is still in progress:
+   * resignation, checkmate (winner is the mating side), stalemate, or one of
+   * the automatic draws — insufficient material, fivefold repetition or the
+   * 75-move rule. Draws have no winner.
+   */
+  public getGameResult(): GameResult | null {
+    if (this.resignedBy !== null) {
+      return {
+        winner: this.resignedBy === Color.White ? Color.Black : Color.White,
+        reason: 'resignation',
+      };
+    }
+    const state = this.getGameState();
+    if (state.result) return state.result;
+    if (state.insufficientMaterial) return { reason: 'insufficient_material' };
+    if (state.repetitionCount >= 5) return { reason: 'repetition' };
+    if (this.halfmoveClock >= 150) return { reason: 'fifty_moves' };
+    return null;
+  }
+
+  private getMissingPieces(): { white: Piece[]; black: Piece[] } {
     // Calculate what pieces should be on the board vs what actually are
     const initialPieces = {
       white: [
@@ -1720,56 +4209,31 @@ export class ChessRules {
   }
 
   private generateFEN(): string {
-    // Simple FEN generation (position only, not full FEN with castling rights etc.)
-    let fen = '';
-
-    for (let rank = 7; rank >= 0; rank--) {
-      let emptySquares = 0;
-      for (let file = 0; file < 8; file++) {
-        const piece = this.board[rank][file];
-        if (piece) {
-          if (emptySquares > 0) {
-            fen += emptySquares.toString();
-            emptySquares = 0;
-          }
-          const pieceChar = this.pieceToFENChar(piece);
-          fen += pieceChar;
-        } else {
-          emptySquares++;
-        }
-      }
-      if (emptySquares > 0) {
-        fen += emptySquares.toString();
-      }
-      if (rank > 0) {
-        fen += '/';
-      }
-    }
-
-    // Add current player
-    fen += ` ${this.currentPlayer === Color.White ? 'w' : 'b'}`;
-
-    // Add castling rights
-    let castling = '';
-    if (this.castlingRights.whiteKingSide) castling += 'K';
-    if (this.castlingRights.whiteQueenSide) castling += 'Q';
-    if (this.castlingRights.blackKingSide) castling += 'k';
-    if (this.castlingRights.blackQueenSide) castling += 'q';
-    fen += ` ${castling || '-'}`;
-
-    // Add en passant target square
-    if (this.enPassantTarget) {
-      const fileChar = String.fromCharCode(97 + this.enPassantTarget.file); // 'a' + file
-      const rankChar = (this.enPassantTarget.rank + 1).toString(); // rank + 1 for 1-based
-      fen += ` ${fileChar}${rankChar}`;
-    } else {
-      fen += ' -';
-    }
+    return `${this.getFenEPD()} ${this.halfmoveClock} ${this.fullmoveNumber}`;
+  }
 
-    // Add halfmove clock and fullmove number
-    fen += ` ${this.halfmoveClock} ${this.fullmoveNumber}`;
+  /**
+   * File (0-7) of the en passant square, or null when there is none or no
+   * pawn of the side to move can actually capture onto it — the same rule
+   * the FEN en passant field follows.
+   */
+  public getEnPassantFile(): number | null {
+    return this.canCaptureEnPassant() ? this.enPassantTarget!.file : null;
+  }
 
-    return fen;
+  /** Whether a pawn of the side to move can reach the en passant square. */
+  private canCaptureEnPassant(): boolean {
+    if (!this.enPassantTarget) return false;
+    const { file, rank } = this.enPassantTarget;
+    const pawnRank = this.currentPlayer === Color.White ? rank - 1 : rank + 1;
+    return [file - 1, file + 1].some(f => {
+      const p = this.getPiece({ file: f, rank: pawnRank });
+      return (
+        p !== null &&
+        p.type === PieceType.Pawn &&
+        p.color === this.currentPlayer
+      );
+    });
   }
 
   private pieceToFENChar(piece: Piece): string {
@@ -1781,11 +4245,103 @@ export class ChessRules {
   /**
    * Load a position from FEN. Validates structure (8x8 board, exactly one
    * king per side, well-formed fields) and returns false without touching
-   * the engine state if the FEN is invalid.
+   * the engine state if the FEN is invalid. Use validateFEN() to find out
+   * why a FEN was rejected. The UCI shorthand "startpos" (any case) loads
+   * the standard starting position.
    */
   public setPosition(fen: string): boolean {
+    const parsed = this.parseFEN(fen);
+    if (typeof parsed === 'string') return false;
+
+    // Everything validated — commit
+    this.cachedGameState = null;
+    this.board = parsed.board;
+    this.currentPlayer = parsed.currentPlayer;
+    this.castlingRights = parsed.castlingRights;
+    this.enPassantTarget = parsed.enPassantTarget;
+    this.halfmoveClock = parsed.halfmoveClock;
+    this.fullmoveNumber = parsed.fullmoveNumber;
+
+    // Clear move history and last move when setting position
+    this.lastMove = null;
+    this.undoStack = [];
+    this.resignedBy = null;
+    this.redoEntries = [];
+    this.moveHistory = [];
+    this.historyEntries = [];
+    this.fenHistory = [this.generateFEN()];
+    this.squareChangedAt = ChessRules.createUnchangedSquares();
+
+    return true;
+  }
+
+  /**
+   * Check a FEN without loading it. Returns null if it is valid, otherwise a
+   * message naming the offending field, e.g. "rank 3 has 9 squares" or
+   * "invalid en passant square 'e9'".
+   */
+  public validateFEN(fen: string): string | null {
+    const parsed = this.parseFEN(fen);
+    return typeof parsed === 'string' ? parsed : null;
+  }
+
+  /**
+   * Load an EPD record: the four board fields of a FEN followed by
+   * semicolon-terminated operations such as `bm Qd1+; id "WAC.001";`.
+   * The halfmove clock and fullmove number default to 0 and 1. Returns the
+   * parsed operations, or null if the position or operations are malformed.
+   */
+  public setPositionFromEPD(epd: string): EPDOperations | null {
+    const fields = epd.trim().split(/\s+/);
+    if (fields.length < 4) return null;
+    const opsText = epd.trim().replace(/^(\S+\s+){3}\S+/, '').trim();
+
+    const all: Record<string, string[]> = {};
+    let tokens: string[] = [];
+    let token = '';
+    let quoted = false;
+    const endToken = () => {
+      if (token) tokens.push(token);
+      token = '';
+    };
+    for (const ch of opsText) {
+      if (quoted) {
+        if (ch === '"') quoted = false;
+        else token += ch;
+      } else if (ch === '"') {
+        quoted = true;
+      } else if (ch === ';') {
+        endToken();
+        if (tokens.length === 0) return null;
+        all[tokens[0]] = tokens.slice(1);
+        tokens = [];
+      } else if (/\s/.test(ch)) {
+        endToken();
+      } else {
+        token += ch;
+      }
+    }
+    endToken();
+    // Every operation must be terminated by a semicolon
+    if (quoted || tokens.length > 0) return null;
+
+    if (!this.setPosition(`${fields.slice(0, 4).join(' ')} 0 1`)) return null;
+    return {
+      bestMoves: all.bm ?? [],
+      id: all.id?.join(' ') ?? null,
+      all,
+    };
+  }
+
+  /** Parse a FEN into engine state, or return an error message. */
+  private parseFEN(fen: string): ParsedFEN | string {
+    // UCI shorthand for the standard starting position
+    if (fen.trim().toLowerCase() === 'startpos') fen = STARTING_FEN;
+
     const parts = fen.trim().split(/\s+/);
-    if (parts.length < 4 || parts.length > 6) return false;
+    if (parts.length < 4 || parts.length > 6) {
+      return `expected 4 to 6 fields, got ${fen.trim() ? parts.length : 0}`;
+    }
 
     const [position, activeColor, castlingField, enPassant] = parts;
     const halfmoveField = parts[4] ?? '0';
@@ -1793,71 +4349,93 @@ export class ChessRules {
 
     // Parse the board into a temporary structure first
     const ranks = position.split('/');
-    if (ranks.length !== 8) return false;
+    if (ranks.length !== 8) {
+      return `piece placement must have 8 ranks, got ${ranks.length}`;
+    }
 
-    const newBoard = this.createEmptyBoard();
+    const board = this.createEmptyBoard();
     let whiteKings = 0;
     let blackKings = 0;
 
     for (let rankIndex = 0; rankIndex < 8; rankIndex++) {
+      const rank = 7 - rankIndex;
       let fileIndex = 0;
       for (const char of ranks[rankIndex]) {
         if (char >= '1' && char <= '8') {
           fileIndex += parseInt(char);
         } else {
           const piece = this.fenCharToPiece(char);
-          if (!piece || fileIndex > 7) return false;
+          if (!piece) return `unknown piece '${char}'`;
           if (piece.type === PieceType.King) {
             if (piece.color === Color.White) whiteKings++;
             else blackKings++;
           }
-          newBoard[7 - rankIndex][fileIndex] = piece;
+          if (fileIndex < 8) board[rank][fileIndex] = piece;
           fileIndex++;
         }
       }
-      if (fileIndex !== 8) return false;
+      if (fileIndex !== 8) {
+        return `rank ${rank + 1} has ${fileIndex} squares`;
+      }
     }
 
-    if (whiteKings !== 1 || blackKings !== 1) return false;
+    if (whiteKings !== 1) {
+      return `white must have exactly one king, got ${whiteKings}`;
+    }
+    if (blackKings !== 1) {
+      return `black must have exactly one king, got ${blackKings}`;
+    }
 
-    if (activeColor !== 'w' && activeColor !== 'b') return false;
+    if (activeColor !== 'w' && activeColor !== 'b') {
+      return `active color must be w or b, got '${activeColor}'`;
+    }
 
-    if (!/^(-|K?Q?k?q?)$/.test(castlingField) || castlingField === '')
-      return false;
+    if (!/^(-|K?Q?k?q?)$/.test(castlingField) || castlingField === '') {
+      return `invalid castling field '${castlingField}'`;
+    }
 
     let enPassantTarget: Position | null = null;
     if (enPassant !== '-') {
-      if (!/^[a-h][36]$/.test(enPassant)) return false;
+      if (!/^[a-h][36]$/.test(enPassant)) {
+        return `invalid en passant square '${enPassant}'`;
+      }
       enPassantTarget = {
         file: enPassant.charCodeAt(0) - 97,
         rank: parseInt(enPassant[1]) - 1,
       };
+      const epError = ChessRules.enPassantTargetError(
+        board,
+        activeColor === 'w' ? Color.White : Color.Black,
+        enPassantTarget
+      );
+      if (epError !== null) {
+        return `invalid en passant square '${enPassant}': ${epError}`;
+      }
     }
 
-    if (!/^\d+$/.test(halfmoveField) || !/^\d+$/.test(fullmoveField))
-      return false;
+    if (!/^\d+$/.test(halfmoveField)) {
+      return `halfmove clock must be a number, got '${halfmoveField}'`;
+    }
+    if (!/^\d+$/.test(fullmoveField)) {
+      return `fullmove number must be a number, got '${fullmoveField}'`;
+    }
+    if (parseInt(fullmoveField) < 1) {
+      return `fullmove number must be at least 1, got '${fullmoveField}'`;
+    }
 
-    // Everything validated — commit
-    this.cachedGameState = null;
-    this.board = newBoard;
-    this.currentPlayer = activeColor === 'w' ? Color.White : Color.Black;
-    this.castlingRights = {
-      whiteKingSide: castlingField.includes('K'),
-      whiteQueenSide: castlingField.includes('Q'),
-      blackKingSide: castlingField.includes('k'),
-      blackQueenSide: castlingField.includes('q'),
+    return {
+      board,
+      currentPlayer: activeColor === 'w' ? Color.White : Color.Black,
+      castlingRights: {
+        whiteKingSide: castlingField.includes('K'),
+        whiteQueenSide: castlingField.includes('Q'),
+        blackKingSide: castlingField.includes('k'),
+        blackQueenSide: castlingField.includes('q'),
+      },
+      enPassantTarget,
+      halfmoveClock: parseInt(halfmoveField),
+      fullmoveNumber: parseInt(fullmoveField),
     };
-    this.enPassantTarget = enPassantTarget;
-    this.halfmoveClock = parseInt(halfmoveField);
-    this.fullmoveNumber = parseInt(fullmoveField);
-
-    // Clear move history and last move when setting position
-    this.lastMove = null;
-    this.moveHistory = [];
-    this.historyEntries = [];
-    this.fenHistory = [this.generateFEN()];
-
-    return true;
   }
 
   private fenCharToPiece(char: string): Piece | null {
@@ -1882,15 +4460,23 @@ export class ChessRules {
     }
   }
 
+  /**
+   * Restore a brand-new game: starting position, White to move, full
+   * castling rights, empty move/undo history and counters at 0/1.
+   */
   public resetGame(): void {
     this.cachedGameState = null;
     this.board = this.createEmptyBoard();
     this.currentPlayer = Color.White;
     this.enPassantTarget = null;
     this.lastMove = null;
+    this.undoStack = [];
+    this.resignedBy = null;
+    this.redoEntries = [];
     this.moveHistory = [];
     this.historyEntries = [];
     this.fenHistory = [];
+    this.squareChangedAt = ChessRules.createUnchangedSquares();
     this.halfmoveClock = 0;
     this.fullmoveNumber = 1;
     this.castlingRights = {
@@ -46,6 +46,8 @@ export interface ChessRulesAPI {
   getFenHistory: () => string[];
   undoToFen: (fen: string, plyCount: number) => boolean;
   parseSAN: (san: string) => Move | null;
+  setMoveComment: (ply: number, comment: string) => boolean;
+  setMoveNags: (ply: number, nags: number[]) => boolean;
 }
 
 export const useChessRules = (): ChessRulesAPI => {
@@ -178,6 +180,28 @@ export const useChessRules = (): ChessRulesAPI => {
     [engine]
   );
 
+  const setMoveComment = useCallback(
+    (ply: number, comment: string): boolean => {
+      const success = engine.setMoveComment(ply, comment);
+      if (success) {
+        triggerUpdate();
+      }
+      return success;
+    },
+    [engine, triggerUpdate]
+  );
+
+  const setMoveNags = useCallback(
+    (ply: number, nags: number[]): boolean => {
+      const success = engine.setMoveNags(ply, nags);
+      if (success) {
+        triggerUpdate();
+      }
+      return success;
+    },
+    [engine, triggerUpdate]
+  );
+
   // Trigger initial update to ensure React knows about the initial board state
   useEffect(() => {
     triggerUpdate();
@@ -201,6 +225,8 @@ export const useChessRules = (): ChessRulesAPI => {
       getFenHistory,
       undoToFen,
       parseSAN,
+      setMoveComment,
+      setMoveNags,
     }),
     [
       getBoardState,
@@ -219,6 +245,8 @@ export const useChessRules = (): ChessRulesAPI => {
       getFenHistory,
       undoToFen,
       parseSAN,
+      setMoveComment,
+      setMoveNags,
     ]
   );
 };
@@ -47,6 +47,13 @@ export type {
   GameResult,
   HistoryEntry,
   MoveType,
+  MatePattern,
+  EPDOperations,
+  PinnedPiece,
+  MoveCallback,
+  MoveClassification,
+  CheckType,
+  ExchangeParticipants,
   GameHistoryEntry,
 } from './types';
 
@@ -54,6 +61,12 @@ export type {
 export {
   squareToPosition,
   positionToSquare,
+  rotatePosition,
+  offsetPosition,
+  rayFrom,
+  raysFrom,
+  positionToIndex,
+  positionFromIndex,
   colorToPlayerColor,
   playerColorToColor,
   pieceTypeToPromotionPiece,
@@ -291,8 +291,24 @@ export type {
   ChessError,
   HistoryEntry,
   MoveType,
+  MatePattern,
+  EPDOperations,
+  PinnedPiece,
+  MoveCallback,
+  MoveClassification,
+  CheckType,
+  ExchangeParticipants,
+} from './engine/chessRules';
+export {
+  PieceType,
+  Color,
+  rotatePosition,
+  offsetPosition,
+  rayFrom,
+  raysFrom,
+  positionToIndex,
+  positionFromIndex,
 } from './engine/chessRules';
-export { PieceType, Color } from './engine/chessRules';
 
 /** Public-facing history entry with algebraic notation */
 export interface GameHistoryEntry {
@@ -305,6 +321,8 @@ export interface GameHistoryEntry {
   isCheck: boolean;
   isCheckmate: boolean;
   algebraic: string;
+  comment?: string;
+  nags?: number[];
 }
 
 /** Piece symbols for figurine algebraic notation */
@@ -1,5 +1,16 @@
 import { describe, it, expect } from 'vitest';
-import { ChessRules, Color, PieceType, Move } from '../src/engine/chessRules';
+import {
+  ChessRules,
+  Color,
+  PieceType,
+  Move,
+  rotatePosition,
+  offsetPosition,
+  rayFrom,
+  raysFrom,
+  positionToIndex,
+  positionFromIndex,
+} from '../src/engine/chessRules';
 
 const FILES = 'abcdefgh';
 
@@ -109,6 +120,177 @@ describe('perft — move generation correctness', () => {
   });
 });
 
+describe('legal move count', () => {
+  it('matches the length of the generated move list', () => {
+    const fens = [
+      fenOf(new ChessRules()),
+      'r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1',
+      'r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1',
+      '7k/5Q2/6K1/8/8/8/8/8 b - - 0 1',
+    ];
+    for (const fen of fens) {
+      const engine = new ChessRules();
+      expect(engine.setPosition(fen)).toBe(true);
+      const count = engine.getLegalMoveCount();
+      expect(count).toBe(engine.getGameState().validMoves.length);
+      expect(engine.getLegalMoveCount()).toBe(count);
+    }
+  });
+
+  it('generates legal moves into a caller-supplied buffer', () => {
+    const engine = new ChessRules();
+    const buffer: Move[] = [];
+    expect(engine.generateLegalMoves(buffer)).toBe(buffer);
+    expect(buffer).toEqual(engine.getGameState().validMoves);
+
+    // Reusing the buffer: the caller clears it, generation appends
+    playSAN(engine, 'e4');
+    buffer.length = 0;
+    engine.generateLegalMoves(buffer);
+    expect(buffer).toEqual(engine.getGameState().validMoves);
+  });
+
+  it('detects whether any capture is available', () => {
+    const engine = new ChessRules();
+    expect(engine.hasCapture()).toBe(false);
+    playSAN(engine, 'e4', 'd5');
+    expect(engine.hasCapture()).toBe(true);
+
+    // En passant is the only capture
+    expect(engine.setPosition('4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1')).toBe(true);
+    expect(engine.hasCapture()).toBe(true);
+
+    // A capturing promotion
+    expect(engine.setPosition('1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1')).toBe(true);
+    expect(engine.hasCapture()).toBe(true);
+
+    // The only capture would expose the king
+    expect(engine.setPosition('4k3/4r3/8/8/3p4/4B3/8/4K3 w - - 0 1')).toBe(
+      true
+    );
+    expect(engine.hasCapture()).toBe(false);
+  });
+});
+
+describe('quiet moves', () => {
+  it('leaves out captures, promotions and checks', () => {
+    const engine = new ChessRules();
+    // Italian middlegame: Bxf7+ and Nxe5 are available to White
+    expect(
+      engine.setPosition(
+        'r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R w KQkq - 1 5'
+      )
+    ).toBe(true);
+    const quiet = engine.getQuietMoves();
+    const sans = quiet.map(move => engine.moveToSAN(move)!);
+    expect(sans.some(san => san.includes('x') || san.includes('+'))).toBe(
+      false
+    );
+    expect(sans).toContain('O-O');
+    expect(quiet.length).toBe(engine.getLegalMoveCount() - 2);
+  });
+
+  it('excludes promotions and en passant', () => {
+    const engine = new ChessRules();
+    const fen = '7k/P7/8/3pP3/8/8/8/4K3 w - d6 0 1';
+    expect(engine.setPosition(fen)).toBe(true);
+    const sans = engine.getQuietMoves().map(move => engine.moveToSAN(move));
+    expect(sans).not.toContain('exd6');
+    expect(sans.some(san => san!.includes('='))).toBe(false);
+    expect(sans).toContain('e6');
+  });
+});
+
+describe('move consistency', () => {
+  it('agrees between the generator and makeMove along random games', () => {
+    for (const fen of [
+      'startpos',
+      'r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1',
+      'n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1',
+    ]) {
+      const engine = new ChessRules();
+      expect(engine.setPosition(fen)).toBe(true);
+      for (let ply = 0; ply < 40; ply++) {
+        expect(engine.verifyMoveConsistency()).toBeNull();
+        const move = engine.randomLegalMove(ply + 1);
+        if (!move) break;
+        engine.makeMove(
+          { file: move.fromFile, rank: move.fromRank },
+          { file: move.toFile, rank: move.toRank },
+          move.promotionPiece
+        );
+      }
+    }
+  });
+});
+
+describe('moves for either color', () => {
+  it('generates moves for the side not to move', () => {
+    const engine = new ChessRules();
+    const black = engine.getLegalMovesForColor(Color.Black);
+    expect(black).toHaveLength(20);
+    expect(black).toContainEqual({
+      fromFile: 6,
+      fromRank: 7,
+      toFile: 5,
+      toRank: 5,
+    });
+    expect(engine.getLegalMovesForColor(Color.White)).toHaveLength(20);
+    expect(engine.getGameState().currentPlayer).toBe(Color.White);
+  });
+
+  it("filters against the given color's king", () => {
+    const engine = new ChessRules();
+    // White to move; the black king on e8 is attacked by the rook on e1
+    expect(engine.setPosition('4k3/8/8/8/8/8/8/K3R3 w - - 0 1')).toBe(true);
+    const black = engine.getLegalMovesForColor(Color.Black);
+    expect(black).toHaveLength(4);
+    expect(black.every(m => m.toFile !== 4)).toBe(true);
+    expect(fenOf(engine)).toBe('4k3/8/8/8/8/8/8/K3R3 w - - 0 1');
+  });
+});
+
+describe('friendly-occupied squares', () => {
+  it('never offers a move onto a square occupied by your own piece', () => {
+    const fens = [
+      'rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1',
+      'rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1',
+      'r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1',
+      'r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1',
+      // Pawns with friendly pieces on both capture diagonals and ahead
+      '4k3/8/8/8/8/2NBN3/3P4/4K3 w - - 0 1',
+      // Every piece type boxed in by its own men
+      '4k3/8/8/2PPP3/2PQP3/2PPP3/8/4K3 w - - 0 1',
+      '4k3/8/8/8/8/8/PPP5/RNK5 w - - 0 1',
+    ];
+    for (const fen of fens) {
+      const engine = new ChessRules();
+      expect(engine.setPosition(fen)).toBe(true);
+      const side = engine.getCurrentPlayer();
+      for (let rank = 0; rank < 8; rank++) {
+        for (let file = 0; file < 8; file++) {
+          const piece = engine.getPiece({ file, rank });
+          if (!piece || piece.color !== side) continue;
+          for (const to of engine.getValidMoves({ file, rank })) {
+            const move = `${FILES[file]}${rank + 1}${FILES[to.file]}${to.rank + 1}`;
+            expect(engine.getPiece(to)?.color, `${fen}: ${move}`).not.toBe(
+              side
+            );
+          }
+        }
+      }
+    }
+  });
+
+  it('leaves a fully boxed-in queen without moves', () => {
+    const engine = new ChessRules();
+    expect(
+      engine.setPosition('4k3/8/8/2PPP3/2PQP3/2PPP3/8/4K3 w - - 0 1')
+    ).toBe(true);
+    expect(engine.getValidMoves(pos('d4'))).toEqual([]);
+  });
+});
+
 describe('castling rights', () => {
   it('revokes castling rights when a rook is captured on its home square', () => {
     const engine = new ChessRules();
@@ -157,6 +339,53 @@ describe('castling rights', () => {
     expect(kingMoves).toContainEqual(pos('c1'));
   });
 
+  it('moves the rook when either side castles either way', () => {
+    const castled = (...moves: string[]) => {
+      const engine = new ChessRules();
+      expect(engine.setPosition('r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1')).toBe(
+        true
+      );
+      playSAN(engine, ...moves);
+      return fenField(engine, 0);
+    };
+    expect(castled('O-O')).toBe('r3k2r/8/8/8/8/8/8/R4RK1');
+    expect(castled('O-O-O')).toBe('r3k2r/8/8/8/8/8/8/2KR3R');
+    expect(castled('Kf1', 'O-O')).toBe('r4rk1/8/8/8/8/8/8/R4K1R');
+    expect(castled('Kf1', 'O-O-O')).toBe('2kr3r/8/8/8/8/8/8/R4K1R');
+  });
+
+  it('forbids castling out of or into check', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('4r1k1/8/8/8/8/8/8/R3K2R w KQ - 0 1')).toBe(
+      true
+    );
+    expect(engine.getValidMoves(pos('e1'))).not.toContainEqual(pos('g1'));
+    expect(engine.getValidMoves(pos('e1'))).not.toContainEqual(pos('c1'));
+    expect(engine.setPosition('6rk/8/8/8/8/8/8/R3K2R w KQ - 0 1')).toBe(true);
+    expect(engine.getValidMoves(pos('e1'))).not.toContainEqual(pos('g1'));
+    expect(engine.getValidMoves(pos('e1'))).toContainEqual(pos('c1'));
+  });
+
+  it('reports whether castling is currently legal per side', () => {
+    const engine = new ChessRules();
+    // Rights intact but pieces still in the way
+    expect(engine.canCastleKingside(Color.White)).toBe(false);
+    expect(engine.canCastleQueenside(Color.Black)).toBe(false);
+
+    // Black rook on f2 covers f1; the b8 knight blocks black's queenside
+    expect(engine.setPosition('rn2k2r/8/8/8/8/8/5r2/R3K2R w KQkq - 0 1')).toBe(
+      true
+    );
+    expect(engine.canCastleKingside(Color.White)).toBe(false);
+    expect(engine.canCastleQueenside(Color.White)).toBe(true);
+    expect(engine.canCastleKingside(Color.Black)).toBe(true);
+    expect(engine.canCastleQueenside(Color.Black)).toBe(false);
+
+    // No rights at all
+    expect(engine.setPosition('r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1')).toBe(true);
+    expect(engine.canCastleKingside(Color.White)).toBe(false);
+  });
+
   it('blocks castling through an attacked square', () => {
     const engine = new ChessRules();
     // Black rook on f2 attacks f1: kingside castling illegal, queenside legal
@@ -165,6 +394,32 @@ describe('castling rights', () => {
     expect(kingMoves).not.toContainEqual(pos('g1'));
     expect(kingMoves).toContainEqual(pos('c1'));
   });
+
+  it('explains why castling is not possible', () => {
+    const status = (fen: string, kingSide = true) => {
+      const engine = new ChessRules();
+      expect(engine.setPosition(fen)).toBe(true);
+      return engine.getCastleStatus(Color.White, kingSide);
+    };
+    expect(status('4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1')).toBeNull();
+    expect(status('4k3/8/8/8/8/8/8/R4K1R w - - 0 1')).toBe('king has moved');
+    expect(status('4k3/8/8/8/8/8/8/R3K2R w Q - 0 1')).toBe('rook has moved');
+    expect(status('4k3/8/8/8/8/8/8/R3K1NR w KQ - 0 1')).toBe(
+      'squares between are occupied'
+    );
+    expect(status('4k3/8/8/8/8/8/8/RN2K2R w KQ - 0 1', false)).toBe(
+      'squares between are occupied'
+    );
+    expect(status('4k3/4r3/8/8/8/8/8/R3K2R w KQ - 0 1')).toBe(
+      'king is in check'
+    );
+    expect(status('4k3/5r2/8/8/8/8/8/R3K2R w KQ - 0 1')).toBe(
+      'king would pass through check'
+    );
+    expect(status('4k3/6r1/8/8/8/8/8/R3K2R w KQ - 0 1')).toBe(
+      'king would end up in check'
+    );
+  });
 });
 
 describe('en passant', () => {
@@ -186,6 +441,47 @@ describe('en passant', () => {
     expect(fenField(engine, 3)).toBe('e3');
   });
 
+  it('clears the target after any other move', () => {
+    const engine = new ChessRules();
+    expect(
+      engine.setPosition(
+        'rnbqkbnr/ppp1pppp/8/8/3p4/8/PPPPPPPP/RNBQKBNR w KQkq - 0 3'
+      )
+    ).toBe(true);
+    playSAN(engine, 'e4', 'Nf6');
+    expect(fenField(engine, 3)).toBe('-');
+    playSAN(engine, 'c4');
+    expect(fenField(engine, 3)).toBe('c3');
+  });
+
+  it('drops a loaded en passant target that no pawn can use', () => {
+    const engine = new ChessRules();
+    expect(
+      engine.setPosition(
+        'rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1'
+      )
+    ).toBe(true);
+    expect(fenField(engine, 3)).toBe('-');
+    expect(engine.getPositionKey()).toBe(
+      'rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq -'
+    );
+  });
+
+  it('reports the en passant file only when the capture is possible', () => {
+    const engine = new ChessRules();
+    expect(engine.getEnPassantFile()).toBeNull();
+    engine.makeMove(pos('e2'), pos('e4'));
+    expect(engine.getEnPassantFile()).toBeNull();
+
+    expect(
+      engine.setPosition(
+        'rnbqkbnr/ppp1pppp/8/8/3p4/8/PPPPPPPP/RNBQKBNR w KQkq - 0 3'
+      )
+    ).toBe(true);
+    engine.makeMove(pos('e2'), pos('e4'));
+    expect(engine.getEnPassantFile()).toBe(4);
+  });
+
   it('executes en passant capture and removes the captured pawn', () => {
     const engine = new ChessRules();
     expect(
@@ -204,6 +500,19 @@ describe('en passant', () => {
     });
   });
 
+  it('captures en passant for white after the double push', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'e4', 'a6', 'e5', 'd5');
+    expect(engine.getValidMoves(pos('e5'))).toContainEqual(pos('d6'));
+    playSAN(engine, 'exd6');
+    expect(engine.getPiece(pos('d5'))).toBeNull();
+    expect(engine.getPiece(pos('d6'))).toEqual({
+      type: PieceType.Pawn,
+      color: Color.White,
+    });
+    expect(engine.getCapturedPieces(Color.Black)).toEqual([PieceType.Pawn]);
+  });
+
   it('rejects en passant that would expose the king along a rank (horizontal pin)', () => {
     const engine = new ChessRules();
     // Ka5, white pawn d5, black pawn c5 (just double-pushed), black queen h5.
@@ -269,6 +578,58 @@ describe('promotion', () => {
     }
   });
 
+  it('rejects a promotion piece on a move that does not promote', () => {
+    const engine = new ChessRules();
+    const result = engine.makeMove(pos('e2'), pos('e4'), PieceType.Queen);
+    expect(result.success).toBe(false);
+    expect(engine.getPiece(pos('e2'))).toEqual({
+      type: PieceType.Pawn,
+      color: Color.White,
+    });
+    expect(
+      engine.isValidMove(pos('g1'), pos('f3'), PieceType.Knight).valid
+    ).toBe(false);
+    expect(engine.isValidMove(pos('g1'), pos('f3')).valid).toBe(true);
+  });
+
+  it('rejects promotion to a king when validating the move', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition(promoFen)).toBe(true);
+    expect(
+      engine.isValidMove(pos('a7'), pos('a8'), PieceType.King).valid
+    ).toBe(false);
+  });
+
+  it('recognises promotion moves before a piece is chosen', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('4k3/P7/8/8/8/8/4P3/R3K3 w - - 0 1')).toBe(true);
+    expect(engine.isPromotionMove(pos('a7'), pos('a8'))).toBe(true);
+    expect(engine.isPromotionMove(pos('e2'), pos('e3'))).toBe(false);
+    expect(engine.isPromotionMove(pos('a1'), pos('a8'))).toBe(false);
+    // Black's pawns are not on move
+    expect(engine.setPosition('4k3/8/8/8/8/8/p7/4K3 w - - 0 1')).toBe(true);
+    expect(engine.isPromotionMove(pos('a2'), pos('a1'))).toBe(false);
+  });
+
+  it('lists legal promotion pieces only for legal promotion moves', () => {
+    const all = [
+      PieceType.Queen,
+      PieceType.Rook,
+      PieceType.Bishop,
+      PieceType.Knight,
+    ];
+    const engine = new ChessRules();
+    expect(engine.setPosition(promoFen)).toBe(true);
+    expect(engine.getLegalPromotions(pos('a7'), pos('a8'))).toEqual(all);
+    expect(engine.getLegalPromotions(pos('a7'), pos('b8'))).toEqual([]);
+    expect(engine.getLegalPromotions(pos('e1'), pos('e2'))).toEqual([]);
+
+    // The b7 pawn is pinned by the c8 bishop: only capturing it is legal
+    expect(engine.setPosition('2b1k3/1P6/K7/8/8/8/8/8 w - - 0 1')).toBe(true);
+    expect(engine.getLegalPromotions(pos('b7'), pos('b8'))).toEqual([]);
+    expect(engine.getLegalPromotions(pos('b7'), pos('c8'))).toEqual(all);
+  });
+
   it('generates all four promotion options in the move list', () => {
     const engine = new ChessRules();
     expect(engine.setPosition(promoFen)).toBe(true);
@@ -285,6 +646,47 @@ describe('promotion', () => {
       ])
     );
   });
+
+  it('details promotion moves per piece for a single pawn', () => {
+    const engine = new ChessRules();
+    const pieces = [
+      PieceType.Queen,
+      PieceType.Rook,
+      PieceType.Bishop,
+      PieceType.Knight,
+    ];
+    expect(engine.setPosition('4k3/P7/8/8/8/8/8/4K3 w - - 0 1')).toBe(true);
+    const pushes = engine.getValidMovesDetailed(pos('a7'));
+    expect(pushes.map(m => m.promotionPiece)).toEqual(pieces);
+    expect(pushes.every(m => m.toFile === 0 && m.toRank === 7)).toBe(true);
+
+    expect(engine.setPosition('1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1')).toBe(true);
+    const moves = engine.getValidMovesDetailed(pos('a7'));
+    expect(moves).toHaveLength(8);
+    const captures = moves.filter(m => m.toFile === 1);
+    expect(captures.map(m => m.promotionPiece)).toEqual(pieces);
+
+    engine.resetGame();
+    const e2 = engine.getValidMovesDetailed(pos('e2'));
+    expect(e2).toHaveLength(2);
+    expect(e2.every(m => m.promotionPiece === undefined)).toBe(true);
+    expect(engine.getValidMovesDetailed(pos('e7'))).toEqual([]);
+  });
+
+  it('expands capture-promotions into all four pieces', () => {
+    const engine = new ChessRules();
+    // The e7 pawn can take the rook on d8; the black king sits on a5
+    expect(engine.setPosition('3r4/4P3/8/k7/8/8/8/4K3 w - - 0 1')).toBe(true);
+    const captures = engine
+      .generateLegalMoves()
+      .filter(m => m.fromFile === 4 && m.toFile === 3 && m.toRank === 7);
+    expect(captures.map(m => engine.moveToSAN(m))).toEqual([
+      'exd8=Q+',
+      'exd8=R',
+      'exd8=B+',
+      'exd8=N',
+    ]);
+  });
 });
 
 describe('FEN parsing and validation', () => {
@@ -318,6 +720,7 @@ describe('FEN parsing and validation', () => {
       '4k3/8/8/8/8/8/8/4K3 w - e9 0 1', // bad ep square
       '4k3/8/8/8/8/8/8/4K3 w - - x 1', // non-numeric halfmove
       '4k3/8/8/8/8/8/8/4K3 w - - 0 y', // non-numeric fullmove
+      '4k3/8/8/8/8/8/8/4K3 w - - 0 0', // fullmove numbers start at 1
     ];
     for (const fen of bad) {
       const engine = new ChessRules();
@@ -325,6 +728,35 @@ describe('FEN parsing and validation', () => {
     }
   });
 
+  it('names the offending field when a FEN is rejected', () => {
+    const engine = new ChessRules();
+    const cases: Array<[string, string]> = [
+      ['8/8/8/8 w - - 0 1', 'piece placement must have 8 ranks, got 4'],
+      ['4k3/8/8/8/8/8/8/4K3 w', 'expected 4 to 6 fields, got 2'],
+      ['4k3/8/8/8/8/8/8/4K3x w - - 0 1', "unknown piece 'x'"],
+      ['4k3/8/8/8/8/8p/8/4K3 w - - 0 1', 'rank 3 has 9 squares'],
+      [
+        '4k3/8/8/8/8/8/8/8 w - - 0 1',
+        'white must have exactly one king, got 0',
+      ],
+      ['4k3/8/8/8/8/8/8/4K3 x - - 0 1', "active color must be w or b, got 'x'"],
+      ['4k3/8/8/8/8/8/8/4K3 w ZZ - 0 1', "invalid castling field 'ZZ'"],
+      ['4k3/8/8/8/8/8/8/4K3 w - e9 0 1', "invalid en passant square 'e9'"],
+      [
+        '4k3/8/8/8/8/8/8/4K3 w - - x 1',
+        "halfmove clock must be a number, got 'x'",
+      ],
+      [
+        '4k3/8/8/8/8/8/8/4K3 w - - 0 0',
+        "fullmove number must be at least 1, got '0'",
+      ],
+    ];
+    for (const [fen, message] of cases) {
+      expect(engine.validateFEN(fen), fen).toBe(message);
+    }
+    expect(engine.validateFEN(fenOf(engine))).toBeNull();
+  });
+
   it('leaves the engine in a usable state after a rejected FEN', () => {
     const engine = new ChessRules();
     const before = fenOf(engine);
@@ -332,9 +764,127 @@ describe('FEN parsing and validation', () => {
     expect(fenOf(engine)).toBe(before);
     expect(engine.getGameState().validMoves).toHaveLength(20);
   });
+
+  it('accepts the UCI startpos shorthand in any case', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'e4');
+    expect(engine.setPosition('startpos')).toBe(true);
+    expect(fenOf(engine)).toBe(fenOf(new ChessRules()));
+    expect(engine.setPosition('  StartPos ')).toBe(true);
+    expect(fenOf(engine)).toBe(fenOf(new ChessRules()));
+    expect(engine.validateFEN('STARTPOS')).toBeNull();
+    expect(engine.setPosition('startpos moves e2e4')).toBe(false);
+  });
+});
+
+describe('pins', () => {
+  it('reports pinned pieces with their pinner and ray', () => {
+    const engine = new ChessRules();
+    expect(engine.getPinnedPieces()).toEqual([]);
+
+    expect(engine.setPosition('4r1k1/8/8/b7/8/4R3/3B4/4K3 w - - 0 1')).toBe(
+      true
+    );
+    const pins = engine.getPinnedPieces();
+    expect(pins.map(p => p.position)).toEqual([pos('e3'), pos('d2')]);
+    expect(pins[0].pinnedBy).toEqual(pos('e8'));
+    expect(pins[1].ray).toEqual([pos('d2'), pos('c3'), pos('b4'), pos('a5')]);
+  });
+
+  it('keeps pinned pieces on the pin ray', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('4r1k1/8/8/b7/8/4R3/3B4/4K3 w - - 0 1')).toBe(
+      true
+    );
+    const rookMoves = engine.getValidMoves(pos('e3'));
+    expect(rookMoves).toHaveLength(6);
+    expect(rookMoves).toContainEqual(pos('e2'));
+    expect(rookMoves).toContainEqual(pos('e8'));
+    expect(rookMoves).not.toContainEqual(pos('d3'));
+    const bishopMoves = engine.getValidMoves(pos('d2'));
+    expect(bishopMoves).toHaveLength(3);
+    expect(bishopMoves).toContainEqual(pos('a5'));
+    expect(bishopMoves).not.toContainEqual(pos('e3'));
+  });
+});
+
+describe('check blocking', () => {
+  it('lists interposition squares for a rook check', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('4r1k1/8/8/8/8/8/3N4/4K3 w - - 0 1')).toBe(true);
+    expect(engine.getCheckBlockSquares()).toEqual([
+      pos('e2'),
+      pos('e3'),
+      pos('e4'),
+      pos('e5'),
+      pos('e6'),
+      pos('e7'),
+    ]);
+  });
+
+  it('lists diagonal squares for a bishop check', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('6k1/8/8/b7/8/8/8/4K3 w - - 0 1')).toBe(true);
+    expect(engine.getCheckBlockSquares()).toEqual([
+      pos('d2'),
+      pos('c3'),
+      pos('b4'),
+    ]);
+  });
+
+  it('is empty for knight checks, contact checks and no check', () => {
+    const engine = new ChessRules();
+    expect(engine.getCheckBlockSquares()).toEqual([]);
+    expect(engine.setPosition('6k1/8/8/8/8/5n2/8/4K3 w - - 0 1')).toBe(true);
+    expect(engine.getCheckBlockSquares()).toEqual([]);
+    expect(engine.setPosition('6k1/8/8/8/8/8/4q3/4K3 w - - 0 1')).toBe(true);
+    expect(engine.getCheckBlockSquares()).toEqual([]);
+  });
+
+  it('never lets the kings stand next to each other', () => {
+    const engine = new ChessRules();
+    for (const side of ['w', 'b']) {
+      expect(engine.setPosition(`8/8/4k3/8/4K3/8/8/8 ${side} - - 0 1`)).toBe(
+        true
+      );
+      const king = side === 'w' ? pos('e4') : pos('e6');
+      const moves = engine.getValidMoves(king);
+      expect(moves).toHaveLength(5);
+      for (const square of ['d5', 'e5', 'f5']) {
+        expect(moves).not.toContainEqual(pos(square));
+      }
+    }
+  });
+
+  it('only offers moves that get out of check', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('4r1k1/8/8/8/8/8/3N4/4K3 w - - 0 1')).toBe(true);
+    // The knight can only interpose; the king must leave the e-file
+    expect(engine.getValidMoves(pos('d2'))).toEqual([pos('e4')]);
+    const kingMoves = engine.getValidMoves(pos('e1'));
+    expect(kingMoves).toHaveLength(3);
+    expect(kingMoves).toContainEqual(pos('d1'));
+    expect(kingMoves).toContainEqual(pos('f1'));
+    expect(kingMoves).toContainEqual(pos('f2'));
+  });
+
+  it('is empty in double check', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('4r1k1/8/8/8/8/3n4/8/4K3 w - - 0 1')).toBe(true);
+    expect(engine.getCheckBlockSquares()).toEqual([]);
+  });
 });
 
 describe('game end detection', () => {
+  it('reports which king is in check', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'e4', 'f5', 'Qh5');
+    expect(engine.isKingInCheck(Color.Black)).toBe(true);
+    expect(engine.isKingInCheck(Color.White)).toBe(false);
+    playSAN(engine, 'g6');
+    expect(engine.isKingInCheck(Color.Black)).toBe(false);
+  });
+
   it("detects fool's mate", () => {
     const engine = new ChessRules();
     playSAN(engine, 'f3', 'e5', 'g4', 'Qh4');
@@ -345,6 +895,35 @@ describe('game end detection', () => {
     expect(history[history.length - 1].algebraic).toBe('Qh4#');
   });
 
+  it('is not checkmate when the checker can be captured or blocked', () => {
+    const engine = new ChessRules();
+    expect(engine.isCheckmate()).toBe(false);
+    playSAN(engine, 'f3', 'e5', 'g4', 'Qh4');
+    expect(engine.isCheckmate()).toBe(true);
+
+    // The king can take the unprotected rook
+    expect(engine.setPosition('6k1/8/8/8/8/8/6r1/6K1 w - - 0 1')).toBe(true);
+    expect(engine.isCheckmate()).toBe(false);
+    // Back-rank check the bishop can block on f1
+    expect(engine.setPosition('6k1/8/8/8/8/8/4BPPP/r5K1 w - - 0 1')).toBe(true);
+    expect(engine.isKingInCheck(Color.White)).toBe(true);
+    expect(engine.isCheckmate()).toBe(false);
+  });
+
+  it('records check and mate flags in the history', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'e4', 'f5', 'Qh5');
+    const check = engine.getHistory()[2];
+    expect(check.isCheck).toBe(true);
+    expect(check.isCheckmate).toBe(false);
+    expect(engine.getHistory()[1].isCheck).toBe(false);
+
+    engine.undoMove();
+    playSAN(engine, 'd4', 'g5', 'Qh5');
+    expect(engine.getHistory()).toHaveLength(5);
+    expect(engine.getHistory()[4].isCheckmate).toBe(true);
+  });
+
   it('detects stalemate', () => {
     const engine = new ChessRules();
     expect(engine.setPosition('7k/5Q2/6K1/8/8/8/8/8 b - - 0 1')).toBe(true);
@@ -354,12 +933,90 @@ describe('game end detection', () => {
     expect(state.isCheck).toBe(false);
   });
 
+  it('detects stalemate with king and pawn against king', () => {
+    const engine = new ChessRules();
+    expect(engine.isStalemate()).toBe(false);
+    expect(engine.setPosition('4k3/4P3/4K3/8/8/8/8/8 b - - 0 1')).toBe(true);
+    expect(engine.isStalemate()).toBe(true);
+    expect(engine.isCheckmate()).toBe(false);
+
+    engine.resetGame();
+    playSAN(engine, 'f3', 'e5', 'g4', 'Qh4');
+    expect(engine.isStalemate()).toBe(false);
+  });
+
   it('counts repetitions', () => {
     const engine = new ChessRules();
     playSAN(engine, 'Nf3', 'Nf6', 'Ng1', 'Ng8', 'Nf3', 'Nf6', 'Ng1', 'Ng8');
     expect(engine.getGameState().repetitionCount).toBe(3);
   });
 
+  it('counts the current occurrence of a position', () => {
+    const engine = new ChessRules();
+    expect(engine.getRepetitionCount()).toBe(1);
+    playSAN(engine, 'Nf3', 'Nf6');
+    expect(engine.getRepetitionCount()).toBe(1);
+    playSAN(engine, 'Ng1', 'Ng8');
+    expect(engine.getRepetitionCount()).toBe(2);
+    // Reached by a null move, so never recorded in the game history
+    expect(engine.makeNullMove()).toBe(true);
+    expect(engine.getRepetitionCount()).toBe(1);
+  });
+
+  it('exposes the repetition key without move counters', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'Nf3', 'Nf6', 'Ng1', 'Ng8');
+    expect(engine.getPositionKey()).toBe(
+      'rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -'
+    );
+    expect(engine.getPositionKey()).toBe(new ChessRules().getPositionKey());
+    expect(fenOf(engine)).not.toBe(fenOf(new ChessRules()));
+  });
+
+  it('shares the EPD between positions differing only in move counters', () => {
+    const a = new ChessRules();
+    const b = new ChessRules();
+    expect(a.setPosition('4k3/8/8/3pP3/8/8/8/4K2R w K d6 0 1')).toBe(true);
+    expect(b.setPosition('4k3/8/8/3pP3/8/8/8/4K2R w K d6 7 42')).toBe(true);
+    expect(a.getFenEPD()).toBe('4k3/8/8/3pP3/8/8/8/4K2R w K d6');
+    expect(b.getFenEPD()).toBe(a.getFenEPD());
+    expect(b.getFenPlacement()).toBe('4k3/8/8/3pP3/8/8/8/4K2R');
+    expect(fenOf(a)).not.toBe(fenOf(b));
+  });
+
+  it('is the FEN without its move counters', () => {
+    const engine = new ChessRules();
+    expect(fenOf(engine)).toBe(`${engine.getFenEPD()} 0 1`);
+    playSAN(engine, 'e4', 'd5', 'e5', 'f5');
+    expect(fenOf(engine)).toBe(`${engine.getFenEPD()} 0 3`);
+  });
+
+  it('gives symmetric positions the same canonical key', () => {
+    const key = (fen: string) => {
+      const engine = new ChessRules();
+      expect(engine.setPosition(fen)).toBe(true);
+      return engine.getCanonicalKey();
+    };
+    const base = key('8/8/4k3/8/2P5/8/1K6/8 w - - 0 1');
+    // Left-right mirror
+    expect(key('8/8/3k4/8/5P2/8/6K1/8 w - - 0 1')).toBe(base);
+    // Colour flip, with Black to move
+    expect(key('8/1k6/8/2p5/8/4K3/8/8 b - - 0 1')).toBe(base);
+    // Same pieces, other side to move
+    expect(key('8/8/4k3/8/2P5/8/1K6/8 b - - 0 1')).not.toBe(base);
+  });
+
+  it('only mirrors left-right when nobody can castle', () => {
+    const engine = new ChessRules();
+    const flipped = new ChessRules();
+    const mirrored = new ChessRules();
+    expect(engine.setPosition('r3k3/8/8/8/8/8/8/4K2R w Kq - 0 1')).toBe(true);
+    expect(flipped.setPosition('4k2r/8/8/8/8/8/8/R3K3 b Qk - 0 1')).toBe(true);
+    expect(mirrored.setPosition('3k3r/8/8/8/8/8/8/R2K4 w - - 0 1')).toBe(true);
+    expect(flipped.getCanonicalKey()).toBe(engine.getCanonicalKey());
+    expect(mirrored.getCanonicalKey()).not.toBe(engine.getCanonicalKey());
+  });
+
   it('tracks the halfmove clock', () => {
     const engine = new ChessRules();
     playSAN(engine, 'Nf3', 'Nf6', 'Ng1', 'Ng8');
@@ -368,6 +1025,40 @@ describe('game end detection', () => {
     expect(engine.getGameState().halfmoveClock).toBe(0);
   });
 
+  it('advances the fullmove number after each Black move', () => {
+    const engine = new ChessRules();
+    expect(engine.getFullmoveNumber()).toBe(1);
+    playSAN(engine, 'e4');
+    expect(engine.getFullmoveNumber()).toBe(1);
+    playSAN(engine, 'e5');
+    expect(engine.getFullmoveNumber()).toBe(2);
+    expect(fenField(engine, 5)).toBe('2');
+    engine.undoMove();
+    expect(engine.getFullmoveNumber()).toBe(1);
+
+    expect(engine.setPosition('4k3/8/8/8/8/8/8/4K3 b - - 0 41')).toBe(true);
+    expect(engine.getFullmoveNumber()).toBe(41);
+    playSAN(engine, 'Kd7');
+    expect(engine.getFullmoveNumber()).toBe(42);
+  });
+
+  it('resets the halfmove clock on captures and pawn moves only', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('4k3/8/8/3p4/8/2N5/8/4K3 w - - 7 30')).toBe(
+      true
+    );
+    expect(engine.getHalfmoveClock()).toBe(7);
+    playSAN(engine, 'Ne2', 'Kd7');
+    expect(engine.getHalfmoveClock()).toBe(9);
+    expect(fenField(engine, 4)).toBe('9');
+    playSAN(engine, 'Nd4', 'Kd6', 'Nb5+', 'Kc5', 'Nc3', 'd4');
+    expect(engine.getHalfmoveClock()).toBe(0);
+    playSAN(engine, 'Ne4+', 'Kd5', 'Kd2', 'Kxe4');
+    expect(engine.getHalfmoveClock()).toBe(0);
+    engine.undoMove();
+    expect(engine.getHalfmoveClock()).toBe(3);
+  });
+
   it('detects insufficient material', () => {
     const insufficient = [
       '4k3/8/8/8/8/8/8/4K3 w - - 0 1', // K vs K
@@ -392,9 +1083,26 @@ describe('game end detection', () => {
       expect(engine.isInsufficientMaterial(), fen).toBe(false);
     }
   });
-});
 
-describe('algebraic notation', () => {
+  it('warns about moves that would stalemate the opponent', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('k7/2K5/8/1Q6/8/8/8/8 w - - 0 1')).toBe(true);
+    const move = (from: string, to: string): Move => ({
+      fromFile: pos(from).file,
+      fromRank: pos(from).rank,
+      toFile: pos(to).file,
+      toRank: pos(to).rank,
+    });
+    expect(engine.moveCausesStalemate(move('b5', 'b6'))).toBe(true);
+    expect(engine.moveCausesStalemate(move('b5', 'b7'))).toBe(false);
+    expect(engine.moveCausesStalemate(move('b5', 'c4'))).toBe(false);
+    expect(engine.moveCausesStalemate(move('b5', 'a8'))).toBeNull();
+    // The probe does not disturb the real game
+    expect(fenOf(engine)).toBe('k7/2K5/8/1Q6/8/8/8/8 w - - 0 1');
+  });
+});
+
+describe('algebraic notation', () => {
   it('disambiguates by file when two knights reach the same square', () => {
     const engine = new ChessRules();
     expect(engine.setPosition('4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1')).toBe(true);
@@ -413,6 +1121,20 @@ describe('algebraic notation', () => {
     expect(engine.getHistory()[2].algebraic).toBe('g8=Q');
   });
 
+  it('renders a single move with its move number', () => {
+    const engine = new ChessRules();
+    const e4 = engine.parseSAN('e4')!;
+    expect(engine.moveToSAN(e4)).toBe('e4');
+    expect(engine.moveToNumberedSAN(e4)).toBe('1. e4');
+    playSAN(engine, 'e4', 'e5', 'Nf3');
+    expect(engine.moveToNumberedSAN(engine.parseSAN('Nc6')!)).toBe('2... Nc6');
+    // Not legal: the position is unchanged
+    expect(
+      engine.moveToSAN({ fromFile: 4, fromRank: 4, toFile: 4, toRank: 3 })
+    ).toBeNull();
+    expect(engine.getHistory()).toHaveLength(3);
+  });
+
   it('parseSAN round-trips generated notation over a full game', () => {
     const moves = [
       'e4',
@@ -449,4 +1171,1412 @@ describe('algebraic notation', () => {
     }
     expect(fenOf(replay)).toBe(fenOf(engine));
   });
+
+  it('lists every legal move in SAN', () => {
+    const engine = new ChessRules();
+    const sans = engine.getLegalMovesSAN();
+    expect(sans).toHaveLength(20);
+    expect(sans).toContain('Nf3');
+    expect(sans).toContain('e4');
+
+    expect(engine.setPosition('4k3/8/8/8/8/8/8/1N2KN1R w K - 0 1')).toBe(true);
+    const withChecks = engine.getLegalMovesSAN();
+    expect(withChecks).toContain('Nbd2');
+    expect(withChecks).toContain('Nfd2');
+    expect(withChecks).toContain('Rh8+');
+    expect(withChecks).toEqual(
+      engine.getGameState().validMoves.map(move => engine.moveToSAN(move))
+    );
+  });
+});
+
+describe('batch move application', () => {
+  it('applies a list of UCI moves', () => {
+    const engine = new ChessRules();
+    expect(engine.applyMoves(['e2e4', 'e7e5', 'g1f3', 'b8c6'])).toBe(4);
+    const expected = new ChessRules();
+    playSAN(expected, 'e4', 'e5', 'Nf3', 'Nc6');
+    expect(fenOf(engine)).toBe(fenOf(expected));
+    expect(engine.getHistory()).toHaveLength(4);
+  });
+
+  it('handles promotions', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('4k3/P7/8/8/8/8/8/4K3 w - - 0 1')).toBe(true);
+    expect(engine.applyMoves(['a7a8n'])).toBe(1);
+    expect(engine.getPiece(pos('a8'))?.type).toBe(PieceType.Knight);
+  });
+
+  it('stops at the first illegal or malformed move', () => {
+    const engine = new ChessRules();
+    expect(engine.applyMoves(['e2e4', 'e7e5', 'e4e5', 'd7d5'])).toBe(2);
+    expect(engine.getHistory()).toHaveLength(2);
+    expect(engine.getCurrentPlayer()).toBe(Color.White);
+
+    engine.resetGame();
+    expect(engine.applyMoves(['e2e4', 'Nf6'])).toBe(1);
+    expect(engine.applyMoves([])).toBe(0);
+  });
+});
+
+describe('UCI position command', () => {
+  it('sets up startpos with moves', () => {
+    const engine = new ChessRules();
+    expect(engine.setPositionUCI('position startpos moves e2e4 e7e5')).toBe(
+      null
+    );
+    const expected = new ChessRules();
+    playSAN(expected, 'e4', 'e5');
+    expect(fenOf(engine)).toBe(fenOf(expected));
+    expect(engine.getHistory()).toHaveLength(2);
+  });
+
+  it('sets up a FEN with and without moves', () => {
+    const engine = new ChessRules();
+    const fen = '4k3/P7/8/8/8/8/8/4K3 w - - 0 1';
+    expect(engine.setPositionUCI(`fen ${fen}`)).toBeNull();
+    expect(fenOf(engine)).toBe(fen);
+    expect(engine.setPositionUCI(`fen ${fen} moves a7a8q e8d7`)).toBeNull();
+    expect(fenOf(engine)).toBe('Q7/3k4/8/8/8/8/8/4K3 w - - 1 2');
+  });
+
+  it('names the rejected token and leaves the engine unchanged', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'd4');
+    const before = fenOf(engine);
+    expect(engine.setPositionUCI('startpos moves e2e4 e2e4')).toBe(
+      "illegal or malformed move 'e2e4'"
+    );
+    expect(engine.setPositionUCI('startpos moves e2e4 xyz')).toBe(
+      "illegal or malformed move 'xyz'"
+    );
+    expect(engine.setPositionUCI('position kiwipete')).toBe(
+      "expected 'startpos' or 'fen', got 'kiwipete'"
+    );
+    expect(engine.setPositionUCI('fen 8/8/8/8 w - - 0 1')).toContain(
+      "invalid FEN '8/8/8/8 w - - 0 1'"
+    );
+    expect(fenOf(engine)).toBe(before);
+  });
+});
+
+describe('material', () => {
+  it('starts balanced', () => {
+    expect(new ChessRules().getMaterialBalance()).toBe(0);
+  });
+
+  it('honours custom piece values', () => {
+    const engine = new ChessRules();
+    // White has an extra knight
+    expect(engine.setPosition('4k3/8/8/8/8/8/8/1N2K3 w - - 0 1')).toBe(true);
+    expect(engine.getMaterialBalance()).toBe(320);
+    engine.setPieceValues(100, 1000, 330, 500, 900);
+    expect(engine.getMaterialBalance()).toBe(1000);
+  });
+
+  it('exposes piece values, including overrides', () => {
+    const engine = new ChessRules();
+    expect(engine.getPieceValue(PieceType.Queen)).toBe(900);
+    expect(engine.getPieceValue(PieceType.King)).toBe(0);
+    engine.setPieceValues(100, 300, 300, 500, 950);
+    expect(engine.getPieceValue(PieceType.Queen)).toBe(950);
+    expect(ChessRules.getBaseValue(PieceType.Queen)).toBe(900);
+
+    const tray = [PieceType.Pawn, PieceType.Queen, PieceType.Knight];
+    tray.sort((a, b) => engine.getPieceValue(b) - engine.getPieceValue(a));
+    expect(tray).toEqual([PieceType.Queen, PieceType.Knight, PieceType.Pawn]);
+  });
+});
+
+describe('game result', () => {
+  it('is null while the game is in progress', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'e4', 'e5');
+    expect(engine.getGameResult()).toBeNull();
+  });
+
+  it('names the mating side as winner on checkmate', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'f3', 'e5', 'g4', 'Qh4');
+    expect(engine.getGameResult()).toEqual({
+      winner: Color.Black,
+      reason: 'checkmate',
+    });
+  });
+
+  it('reports stalemate and automatic draws without a winner', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('k7/2K5/1Q6/8/8/8/8/8 b - - 0 1')).toBe(true);
+    expect(engine.getGameResult()).toEqual({ reason: 'stalemate' });
+
+    expect(engine.setPosition('4k3/8/8/8/8/8/8/4KB2 w - - 0 1')).toBe(true);
+    expect(engine.getGameResult()).toEqual({
+      reason: 'insufficient_material',
+    });
+
+    expect(engine.setPosition('4k3/8/8/8/8/8/8/R3K3 w - - 150 90')).toBe(
+      true
+    );
+    expect(engine.getGameResult()).toEqual({ reason: 'fifty_moves' });
+  });
+
+  it('awards the game to the opponent of the resigning side', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'e4');
+    expect(engine.resign(Color.Black)).toBe(true);
+    expect(engine.getGameResult()).toEqual({
+      winner: Color.White,
+      reason: 'resignation',
+    });
+    expect(engine.toPGN()).toContain('[Result "1-0"]');
+    // A finished game cannot be resigned again
+    expect(engine.resign(Color.White)).toBe(false);
+
+    engine.resetGame();
+    expect(engine.getGameResult()).toBeNull();
+  });
+
+  it('withdraws a resignation when play goes on or a move is undone', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'e4');
+    expect(engine.resign(Color.Black)).toBe(true);
+    playSAN(engine, 'e5');
+    expect(engine.getGameResult()).toBeNull();
+
+    expect(engine.resign(Color.White)).toBe(true);
+    expect(engine.undoMove()).toBe(true);
+    expect(engine.getGameResult()).toBeNull();
+    expect(engine.getCurrentPlayer()).toBe(Color.Black);
+  });
+});
+
+describe('captured pieces', () => {
+  it('lists captured pieces per color from the history', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'e4', 'd5', 'exd5', 'Nf6', 'd4', 'Nxd5', 'c4', 'e5');
+    playSAN(engine, 'cxd5', 'exd4');
+    expect(engine.getCapturedPieces(Color.Black)).toEqual([
+      PieceType.Pawn,
+      PieceType.Knight,
+    ]);
+    expect(engine.getCapturedPieces(Color.White)).toEqual([
+      PieceType.Pawn,
+      PieceType.Pawn,
+    ]);
+  });
+
+  it('counts a captured promoted piece as the promoted type', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('4k3/P7/8/8/8/8/r7/4K3 w - - 0 1')).toBe(true);
+    playSAN(engine, 'a8=Q+', 'Rxa8');
+    expect(engine.getCapturedPieces(Color.White)).toEqual([PieceType.Queen]);
+    expect(engine.getCapturedPieces(Color.Black)).toEqual([]);
+  });
+});
+
+describe('game phase', () => {
+  it('runs from 24 in the opening to 0 in a pawn ending', () => {
+    const engine = new ChessRules();
+    expect(engine.getGamePhase()).toBe(24);
+    expect(engine.setPosition('4k3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1')).toBe(
+      true
+    );
+    expect(engine.getGamePhase()).toBe(0);
+    expect(engine.setPosition('3rk3/8/8/8/8/8/8/1N1QK3 w - - 0 1')).toBe(true);
+    expect(engine.getGamePhase()).toBe(7);
+  });
+
+  it('caps extra promoted material at 24', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('QQQQkQQQ/8/8/8/8/8/8/qqqqKqqq w - - 0 1')).toBe(
+      true
+    );
+    expect(engine.getGamePhase()).toBe(24);
+  });
+});
+
+describe('development', () => {
+  it('counts minor pieces off their starting squares', () => {
+    const engine = new ChessRules();
+    expect(engine.getDevelopedPieces(Color.White)).toBe(0);
+    playSAN(engine, 'e4', 'e5', 'Nf3', 'Nc6', 'Bc4', 'Nf6', 'O-O');
+    expect(engine.getDevelopedPieces(Color.White)).toBe(2);
+    expect(engine.getDevelopedPieces(Color.Black)).toBe(2);
+    expect(engine.hasCastled(Color.White)).toBe(true);
+    expect(engine.hasCastled(Color.Black)).toBe(false);
+
+    // A knight that returns home is undeveloped again
+    playSAN(engine, 'Ng8');
+    expect(engine.getDevelopedPieces(Color.Black)).toBe(1);
+  });
+});
+
+describe('pawn structure', () => {
+  it('finds open and half-open files', () => {
+    const engine = new ChessRules();
+    expect(engine.getOpenFiles()).toEqual([]);
+    expect(engine.getHalfOpenFiles(Color.White)).toEqual([]);
+
+    // e-file cleared of both pawns; white's d-pawn is gone too
+    expect(
+      engine.setPosition(
+        'rnbqkbnr/pppp1ppp/8/8/8/8/PPP2PPP/RNBQKBNR w KQkq - 0 1'
+      )
+    ).toBe(true);
+    expect(engine.getOpenFiles()).toEqual([4]);
+    expect(engine.getHalfOpenFiles(Color.White)).toEqual([3]);
+    expect(engine.getHalfOpenFiles(Color.Black)).toEqual([]);
+  });
+
+  it('flags a back rank an enemy rook can reach', () => {
+    const weak = (fen: string) => {
+      const engine = new ChessRules();
+      expect(engine.setPosition(fen)).toBe(true);
+      return engine.isBackRankWeak(Color.White);
+    };
+    // King behind unmoved pawns, but no file is free of white pawns
+    expect(weak('3r2k1/ppp2ppp/8/8/8/8/PPPPPPPP/6K1 w - - 0 1')).toBe(false);
+    // Open d- and e-files and no escape square
+    expect(weak('3r2k1/ppp2ppp/8/8/8/8/PPP2PPP/6K1 w - - 0 1')).toBe(true);
+    // Luft on h2
+    expect(weak('3r2k1/ppp2ppp/8/8/8/7P/PPP2PP1/6K1 w - - 0 1')).toBe(false);
+    // Luft on h2, but the bishop on d6 covers it
+    expect(weak('3r2k1/ppp2ppp/3b4/8/8/7P/PPP2PP1/6K1 w - - 0 1')).toBe(true);
+    // No enemy rook or queen
+    expect(weak('6k1/ppp2ppp/8/8/8/8/PPP2PPP/6K1 w - - 0 1')).toBe(false);
+  });
+});
+
+describe('board queries', () => {
+  it('tells whether a square is attacked by a color', () => {
+    const engine = new ChessRules();
+    // Pawns only attack diagonally forward
+    expect(engine.isSquareAttacked(pos('d3'), Color.White)).toBe(true);
+    expect(engine.isSquareAttacked(pos('e4'), Color.White)).toBe(false);
+    expect(engine.isSquareAttacked(pos('d6'), Color.Black)).toBe(true);
+    expect(engine.isSquareAttacked(pos('d3'), Color.Black)).toBe(false);
+    // Own pieces count as attacked (defended)
+    expect(engine.isSquareAttacked(pos('e2'), Color.White)).toBe(true);
+
+    // The rook's ray stops at the knight on e4
+    expect(engine.setPosition('4k3/8/8/8/4N3/8/8/4R1K1 w - - 0 1')).toBe(true);
+    expect(engine.isSquareAttacked(pos('e4'), Color.White)).toBe(true);
+    expect(engine.isSquareAttacked(pos('e5'), Color.White)).toBe(false);
+    expect(engine.isSquareAttacked(pos('f6'), Color.White)).toBe(true);
+  });
+
+  it('lists occupied squares in rank-then-file order', () => {
+    const engine = new ChessRules();
+    const pieces = engine.getPieces();
+    expect(pieces).toHaveLength(32);
+    expect(pieces[0]).toEqual({
+      position: pos('a1'),
+      piece: { type: PieceType.Rook, color: Color.White },
+    });
+    expect(pieces[31].position).toEqual(pos('h8'));
+
+    expect(engine.setPosition('4k3/8/8/8/8/8/8/4K3 w - - 0 1')).toBe(true);
+    expect(engine.getPieces().map(p => p.position)).toEqual([
+      pos('e1'),
+      pos('e8'),
+    ]);
+  });
+
+  it('reports controlled squares as a bitmask per color', () => {
+    const bit = (square: string) => {
+      const { file, rank } = pos(square);
+      return 1n << BigInt(rank * 8 + file);
+    };
+    const engine = new ChessRules();
+    const white = engine.getControlledSquares(Color.White);
+    expect(white & bit('d3')).not.toBe(0n);
+    expect(white & bit('e4')).toBe(0n);
+    expect(white & bit('b1')).not.toBe(0n);
+
+    playSAN(engine, 'e4', 'e5', 'Nc3', 'Nc6', 'Nf3', 'Nf6');
+    const whiteAfter = engine.getControlledSquares(Color.White);
+    const blackAfter = engine.getControlledSquares(Color.Black);
+    for (const square of ['d4', 'd5', 'e4', 'e5']) {
+      expect(whiteAfter & bit(square)).not.toBe(0n);
+      expect(blackAfter & bit(square)).not.toBe(0n);
+    }
+  });
+
+  it("lists the side to move's pieces that are under attack", () => {
+    const engine = new ChessRules();
+    expect(engine.getThreatenedPieces()).toEqual([]);
+    playSAN(engine, 'e4', 'd5');
+    expect(engine.getThreatenedPieces()).toEqual([pos('e4')]);
+    // Defended pieces count too: the king guards the attacked knight
+    expect(engine.setPosition('4k3/8/8/3q4/8/5N2/6K1/8 w - - 0 1')).toBe(true);
+    expect(engine.getThreatenedPieces()).toEqual([pos('f3')]);
+  });
+
+  it('lists attackers of a square, cheapest first', () => {
+    const engine = new ChessRules();
+    expect(
+      engine.setPosition('4k3/8/8/3r4/4P3/1QN5/8/3RK3 w - - 0 1')
+    ).toBe(true);
+    expect(engine.getAttackers(pos('d5'), Color.White)).toEqual([
+      pos('e4'),
+      pos('c3'),
+      pos('d1'),
+      pos('b3'),
+    ]);
+    expect(engine.getAttackers(pos('e4'), Color.Black)).toEqual([]);
+    expect(engine.getAttackers(pos('d1'), Color.Black)).toEqual([pos('d5')]);
+  });
+});
+
+describe('board bytes', () => {
+  it('encodes the starting position rank by rank from a1', () => {
+    const bytes = new ChessRules().getBoardBytes();
+    expect(bytes).toHaveLength(64);
+    expect(Array.from(bytes.subarray(0, 8))).toEqual([2, 3, 4, 5, 6, 4, 3, 2]);
+    expect(Array.from(bytes.subarray(8, 16))).toEqual(Array(8).fill(1));
+    expect(Array.from(bytes.subarray(16, 48))).toEqual(Array(32).fill(0));
+    expect(Array.from(bytes.subarray(48, 56))).toEqual(Array(8).fill(7));
+    expect(Array.from(bytes.subarray(56))).toEqual([
+      8, 9, 10, 11, 12, 10, 9, 8,
+    ]);
+  });
+});
+
+describe('binary positions', () => {
+  it('round-trips positions exactly', () => {
+    for (const fen of [
+      'rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1',
+      'rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w Kq f6 0 3',
+      'rnbqkbnr/pppp1ppp/8/8/3Pp3/8/PPP1PPPP/RNBQKBNR b KQkq d3 0 2',
+      '8/2k5/8/8/8/8/4K3/8 b - - 99 312',
+    ]) {
+      const engine = new ChessRules();
+      expect(engine.setPosition(fen)).toBe(true);
+      const bytes = engine.toBytes();
+      expect(bytes).toHaveLength(38);
+      expect(fenOf(ChessRules.fromBytes(bytes)!)).toBe(fen);
+    }
+  });
+
+  it('rejects input of the wrong length or with a bad position', () => {
+    const bytes = new ChessRules().toBytes();
+    expect(ChessRules.fromBytes(bytes.subarray(0, 37))).toBeNull();
+    // Both kings removed (e1 is byte 2's low nibble, e8 byte 30's)
+    bytes[2] &= 0xf0;
+    bytes[30] &= 0xf0;
+    expect(ChessRules.fromBytes(bytes)).toBeNull();
+    expect(ChessRules.fromBytes(new Uint8Array(38).fill(0xff))).toBeNull();
+  });
+});
+
+describe('board orientation', () => {
+  it('rotates a square to the opposite side of the board', () => {
+    expect(rotatePosition(pos('e4'))).toEqual(pos('d5'));
+    expect(rotatePosition(pos('a1'))).toEqual(pos('h8'));
+  });
+
+  it('is its own inverse', () => {
+    for (let rank = 0; rank < 8; rank++) {
+      for (let file = 0; file < 8; file++) {
+        const square = { file, rank };
+        expect(rotatePosition(rotatePosition(square))).toEqual(square);
+      }
+    }
+  });
+});
+
+describe('position helpers', () => {
+  it('offsets within the board and returns null off it', () => {
+    expect(offsetPosition(pos('e4'), 1, 2)).toEqual(pos('f6'));
+    expect(offsetPosition(pos('a1'), -1, 0)).toBeNull();
+    expect(offsetPosition(pos('h8'), 0, 1)).toBeNull();
+  });
+
+  it('walks rays to the board edge', () => {
+    expect(rayFrom(pos('c3'), -1, -1)).toEqual([pos('b2'), pos('a1')]);
+    expect(rayFrom(pos('h4'), 1, 0)).toEqual([]);
+    expect(rayFrom(pos('e4'), 0, 0)).toEqual([]);
+    expect(rayFrom(pos('e2'), 0, 1)).toHaveLength(6);
+
+    const rays = raysFrom(pos('a1'));
+    expect(rays.map(ray => ray.length)).toEqual([7, 7, 7, 0, 0, 0, 0, 0]);
+    expect(rays[1][6]).toEqual(pos('h8'));
+    expect(raysFrom(pos('d4')).flat()).toHaveLength(27);
+  });
+
+  it('round-trips every square through its index', () => {
+    expect(positionToIndex(pos('a1'))).toBe(0);
+    expect(positionToIndex(pos('h1'))).toBe(7);
+    expect(positionToIndex(pos('h8'))).toBe(63);
+    for (let index = 0; index < 64; index++) {
+      expect(positionToIndex(positionFromIndex(index)!)).toBe(index);
+    }
+    expect(positionFromIndex(64)).toBeNull();
+    expect(positionFromIndex(-1)).toBeNull();
+    expect(positionFromIndex(1.5)).toBeNull();
+  });
+});
+
+describe('reset', () => {
+  it('clears history and counters when reusing an engine', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'e4', 'e5', 'Nf3', 'Nc6', 'Bb5');
+    engine.resetGame();
+
+    expect(engine.getGameState().moveHistory).toEqual([]);
+    expect(engine.getHistory()).toEqual([]);
+    expect(engine.getFenHistory()).toHaveLength(1);
+    expect(engine.getLastMove()).toBeNull();
+    expect(fenField(engine, 4)).toBe('0');
+    expect(fenField(engine, 5)).toBe('1');
+    expect(fenOf(engine)).toBe(fenOf(new ChessRules()));
+    expect(engine.undoMove()).toBe(false);
+  });
+});
+
+describe('empty board', () => {
+  it('starts with no pieces and no castling rights', () => {
+    const engine = ChessRules.empty();
+    expect(fenOf(engine)).toBe('8/8/8/8/8/8/8/8 w - - 0 1');
+    expect(engine.getHistory()).toEqual([]);
+    expect(engine.getLegalMoveCount()).toBe(0);
+  });
+
+  it('has no king in check', () => {
+    const engine = ChessRules.empty();
+    expect(engine.isKingInCheck(Color.White)).toBe(false);
+    expect(engine.isKingInCheck(Color.Black)).toBe(false);
+  });
+});
+
+describe('undo', () => {
+  it('restores the exact FEN after every kind of move', () => {
+    const cases: Array<[string, string]> = [
+      ['r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 5 20', 'O-O-O'],
+      ['r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 5 20', 'O-O'],
+      ['r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 5 20', 'Rxa8+'],
+      ['4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 9', 'exd6'],
+      ['1n2k3/P7/8/8/8/8/8/4K3 w - - 3 40', 'axb8=N'],
+      ['4k3/8/8/8/8/8/4p3/3QK3 b - - 0 40', 'exd1=Q+'],
+    ];
+    for (const [fen, san] of cases) {
+      const engine = new ChessRules();
+      expect(engine.setPosition(fen)).toBe(true);
+      playSAN(engine, san);
+      expect(fenOf(engine), san).not.toBe(fen);
+      expect(engine.undoMove()).toBe(true);
+      expect(fenOf(engine), san).toBe(fen);
+    }
+  });
+
+  it('walks a whole game back to the start', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'e4', 'd5', 'exd5', 'Qxd5', 'Nc3', 'Qa5', 'd4', 'c6');
+    playSAN(engine, 'Nf3', 'Bg4', 'Bf4', 'e6', 'h3', 'Bxf3', 'Qxf3', 'Bb4');
+    playSAN(engine, 'Be2', 'Nd7', 'a3', 'O-O-O', 'axb4', 'Qxa1+', 'Kd2');
+    const fens = engine.getFenHistory();
+    for (let ply = fens.length - 1; ply > 0; ply--) {
+      expect(fenOf(engine)).toBe(fens[ply]);
+      expect(engine.undoMove()).toBe(true);
+    }
+    expect(fenOf(engine)).toBe(fenOf(new ChessRules()));
+    expect(engine.getHistory()).toEqual([]);
+    expect(engine.undoMove()).toBe(false);
+  });
+});
+
+describe('clone', () => {
+  it('produces an independent copy of position and history', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'e4', 'e5', 'Nf3');
+    const copy = engine.clone();
+    expect(fenOf(copy)).toBe(fenOf(engine));
+    expect(copy.getHistory()).toEqual(engine.getHistory());
+
+    playSAN(copy, 'Nc6', 'Bb5');
+    expect(engine.getHistory()).toHaveLength(3);
+    expect(engine.getPiece(pos('c6'))).toBeNull();
+    expect(engine.getCurrentPlayer()).toBe(Color.Black);
+
+    // Undoing in either engine leaves the other alone
+    expect(copy.undoMove()).toBe(true);
+    expect(engine.undoMove()).toBe(true);
+    expect(engine.undoMove()).toBe(true);
+    expect(copy.getPiece(pos('c6'))).toEqual({
+      type: PieceType.Knight,
+      color: Color.Black,
+    });
+    expect(copy.getHistory()).toHaveLength(4);
+    expect(engine.getHistory()).toHaveLength(1);
+  });
+});
+
+describe('null move', () => {
+  it('passes the turn and clears the en passant target', () => {
+    const engine = new ChessRules();
+    expect(
+      engine.setPosition(
+        'rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3'
+      )
+    ).toBe(true);
+    const before = fenOf(engine);
+    expect(engine.makeNullMove()).toBe(true);
+    expect(engine.getCurrentPlayer()).toBe(Color.White);
+    expect(fenField(engine, 3)).toBe('-');
+    expect(engine.undoMove()).toBe(true);
+    expect(fenOf(engine)).toBe(before);
+  });
+
+  it('is refused while in check', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'e4', 'f5', 'Qh5+');
+    const before = fenOf(engine);
+    expect(engine.makeNullMove()).toBe(false);
+    expect(fenOf(engine)).toBe(before);
+  });
+});
+
+describe('PGN export', () => {
+  it('emits the Seven Tag Roster with defaults and a computed result', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'f3', 'e5', 'g4', 'Qh4');
+    const pgn = engine.toPGN({
+      White: 'Fool',
+      Black: 'Sage',
+      Result: '1-0',
+      Annotator: 'Me',
+    });
+    expect(pgn).toBe(
+      [
+        '[Event "?"]',
+        '[Site "?"]',
+        '[Date "?"]',
+        '[Round "?"]',
+        '[White "Fool"]',
+        '[Black "Sage"]',
+        '[Result "0-1"]',
+        '[Annotator "Me"]',
+        '',
+        '1. f3 e5 2. g4 Qh4# 0-1',
+        '',
+      ].join('\n')
+    );
+  });
+
+  it('records a result the engine does not track', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'e4', 'e5');
+    const pgn = engine.toPGN({ Date: '2024.01.31' }, { reason: 'draw' });
+    expect(pgn).toContain('[Date "2024.01.31"]');
+    expect(pgn).toContain('[Result "1/2-1/2"]');
+    expect(pgn.endsWith('\n1. e4 e5 1/2-1/2\n')).toBe(true);
+
+    const timeout = { winner: Color.Black, reason: 'timeout' as const };
+    expect(engine.toPGN({}, timeout)).toContain('[Result "0-1"]');
+    expect(engine.toPGN({}, null)).toContain('[Result "*"]');
+  });
+
+  it('records a non-standard start and numbers Black-first games', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('4k3/8/8/8/8/8/8/4K2R b K - 0 12')).toBe(true);
+    playSAN(engine, 'Kd7', 'O-O');
+    const pgn = engine.toPGN();
+    expect(pgn).toContain('[SetUp "1"]');
+    expect(pgn).toContain('[FEN "4k3/8/8/8/8/8/8/4K2R b K - 0 12"]');
+    expect(pgn.endsWith('\n\n12... Kd7 13. O-O *\n')).toBe(true);
+  });
+
+  it('emits NAGs and comments after their moves', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'e4', 'e5', 'Qh5');
+    expect(engine.setMoveComment(0, 'Best by test')).toBe(true);
+    expect(engine.setMoveNags(2, [6])).toBe(true);
+    expect(engine.setMoveComment(2, '{too early}')).toBe(true);
+    expect(engine.toPGN()).toContain(
+      '\n1. e4 {Best by test} 1... e5 2. Qh5 $6 {too early} *\n'
+    );
+
+    expect(engine.setMoveComment(0, '')).toBe(true);
+    expect(engine.setMoveNags(2, [])).toBe(true);
+    expect(engine.toPGN()).toContain('\n1. e4 e5 2. Qh5 {too early} *\n');
+  });
+
+  it('round-trips comments and NAGs through loadPGN', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('4k3/8/8/8/8/8/8/4K2R b K - 0 12')).toBe(true);
+    playSAN(engine, 'Kd7', 'O-O', 'Ke6', 'Rf2', 'Kd5');
+    expect(engine.setMoveComment(0, 'Heading for the centre')).toBe(true);
+    expect(engine.setMoveNags(1, [1, 14])).toBe(true);
+    expect(engine.setMoveComment(3, 'Cutting the king off')).toBe(true);
+    expect(engine.setMoveNags(4, [2])).toBe(true);
+
+    const annotations = (e: ChessRules) =>
+      e.getHistory().map(({ algebraic, comment, nags }) => ({
+        algebraic,
+        comment,
+        nags,
+      }));
+    const loaded = new ChessRules();
+    expect(loaded.loadPGN(engine.toPGN())).toBe(true);
+    expect(annotations(loaded)).toEqual(annotations(engine));
+    expect(fenOf(loaded)).toBe(fenOf(engine));
+    expect(loaded.toPGN()).toBe(engine.toPGN());
+  });
+
+  it('leaves the engine untouched when a PGN does not load', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'e4');
+    const before = fenOf(engine);
+    expect(engine.loadPGN('1. e4 e5 2. Ke3 *')).toBe(false);
+    expect(engine.loadPGN('[FEN "not a fen"]\n\n1. e4 *')).toBe(false);
+    expect(fenOf(engine)).toBe(before);
+    expect(engine.getHistory()).toHaveLength(1);
+  });
+
+  it('rejects annotations for missing moves or invalid NAGs', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'e4');
+    expect(engine.setMoveComment(1, 'nothing here')).toBe(false);
+    expect(engine.setMoveNags(-1, [1])).toBe(false);
+    expect(engine.setMoveNags(0, [256])).toBe(false);
+    expect(engine.setMoveNags(0, [1.5])).toBe(false);
+    expect(engine.getHistory()[0].nags).toBeUndefined();
+  });
+});
+
+describe('random legal moves', () => {
+  it('is deterministic for a given seed', () => {
+    const engine = new ChessRules();
+    expect(engine.randomLegalMove(42)).toEqual(engine.randomLegalMove(42));
+    const picks = new Set(
+      [1, 2, 3, 4, 5, 6, 7, 8].map(seed =>
+        JSON.stringify(engine.randomLegalMove(seed))
+      )
+    );
+    expect(picks.size).toBeGreaterThan(1);
+  });
+
+  it('returns null when there is no legal move', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('7k/5Q2/6K1/8/8/8/8/8 b - - 0 1')).toBe(true);
+    expect(engine.randomLegalMove(1)).toBeNull();
+  });
+
+  it('plays random games without ever producing a rejected move', () => {
+    for (let game = 0; game < 5; game++) {
+      const engine = new ChessRules();
+      for (let ply = 0; ply < 80; ply++) {
+        const move = engine.randomLegalMove(game * 1000 + ply);
+        if (!move) break;
+        const result = engine.makeMove(
+          { file: move.fromFile, rank: move.fromRank },
+          { file: move.toFile, rank: move.toRank },
+          move.promotionPiece
+        );
+        expect(result.success).toBe(true);
+      }
+    }
+  });
+});
+
+describe('mate patterns', () => {
+  it('recognises a back-rank mate', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1')).toBe(true);
+    playSAN(engine, 'Ra8');
+    expect(engine.getMatePattern()).toBe('back_rank');
+  });
+
+  it('recognises a smothered mate', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('6rk/6pp/7N/8/8/8/8/6K1 w - - 0 1')).toBe(true);
+    playSAN(engine, 'Nf7');
+    expect(engine.getMatePattern()).toBe('smothered');
+  });
+
+  it("recognises Anastasia's mate", () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('8/4N1pk/8/8/8/8/8/K2R4 w - - 0 1')).toBe(true);
+    playSAN(engine, 'Rh1');
+    expect(engine.getMatePattern()).toBe('anastasia');
+  });
+
+  it('returns null for unnamed mates and positions that are not mate', () => {
+    const engine = new ChessRules();
+    expect(engine.getMatePattern()).toBeNull();
+    playSAN(engine, 'f3', 'e5', 'g4', 'Qh4');
+    expect(engine.getGameState().result?.reason).toBe('checkmate');
+    expect(engine.getMatePattern()).toBeNull();
+  });
+});
+
+describe('getGameHash', () => {
+  it('depends on the move sequence, not just the position', () => {
+    const a = new ChessRules();
+    const b = new ChessRules();
+    const c = new ChessRules();
+    expect(a.getGameHash()).toBe(0xcbf29ce484222325n);
+
+    playSAN(a, 'Nf3', 'Nf6', 'g3');
+    playSAN(b, 'Nf3', 'Nf6', 'g3');
+    playSAN(c, 'g3', 'Nf6', 'Nf3');
+    expect(a.getGameHash()).toBe(b.getGameHash());
+    expect(c.getPositionKey()).toBe(a.getPositionKey());
+    expect(c.getGameHash()).not.toBe(a.getGameHash());
+    expect(a.getGameHash() < 1n << 64n).toBe(true);
+
+    const shorter = new ChessRules();
+    playSAN(shorter, 'Nf3', 'Nf6');
+    a.undoMove();
+    expect(a.getGameHash()).toBe(shorter.getGameHash());
+  });
+});
+
+describe('setEnPassantTarget', () => {
+  it('enables an en passant capture on a set-up position', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('4k3/8/8/3pP3/8/8/8/4K3 w - - 0 1')).toBe(true);
+    expect(engine.parseSAN('exd6')).toBeNull();
+
+    expect(engine.setEnPassantTarget(pos('d6'))).toBe(true);
+    expect(fenField(engine, 3)).toBe('d6');
+    playSAN(engine, 'exd6');
+    expect(engine.getPiece(pos('d5'))).toBeNull();
+  });
+
+  it('rejects squares off the third and sixth ranks', () => {
+    const engine = new ChessRules();
+    expect(engine.setEnPassantTarget(pos('e4'))).toBe(false);
+    expect(engine.setEnPassantTarget({ file: 8, rank: 2 })).toBe(false);
+    expect(engine.setEnPassantTarget(null)).toBe(true);
+  });
+
+  it('rejects squares that do not fit the side to move', () => {
+    const engine = new ChessRules();
+    // White to move: e3 would let d2 or f2 take White's own e2 pawn
+    expect(engine.setEnPassantTarget(pos('e3'))).toBe(false);
+    expect(engine.parseSAN('dxe3')).toBeNull();
+    expect(engine.getPiece(pos('e2'))?.color).toBe(Color.White);
+
+    expect(engine.setPosition('4k3/8/8/3pP3/8/8/8/4K3 w - - 0 1')).toBe(true);
+    expect(engine.setEnPassantTarget(pos('e6'))).toBe(false); // own pawn
+    expect(engine.setEnPassantTarget(pos('c6'))).toBe(false); // no pawn
+    expect(engine.setEnPassantTarget(pos('d3'))).toBe(false); // wrong side
+    expect(engine.setPosition('4k3/8/3n4/3pP3/8/8/8/4K3 w - - 0 1')).toBe(true);
+    expect(engine.setEnPassantTarget(pos('d6'))).toBe(false); // occupied
+    expect(fenField(engine, 3)).toBe('-');
+  });
+
+  it('rejects FENs whose target does not fit the side to move', () => {
+    const engine = new ChessRules();
+    const before = fenOf(engine);
+    const cases: Array<[string, string]> = [
+      [
+        'rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e3 0 1',
+        "invalid en passant square 'e3': must be on rank 6 with White to move",
+      ],
+      [
+        'rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e6 0 2',
+        "invalid en passant square 'e6': must be on rank 3 with Black to move",
+      ],
+      [
+        'rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq e3 0 1',
+        "invalid en passant square 'e3': no enemy pawn in front of it",
+      ],
+    ];
+    for (const [fen, message] of cases) {
+      expect(engine.setPosition(fen), fen).toBe(false);
+      expect(engine.validateFEN(fen)).toBe(message);
+    }
+    expect(fenOf(engine)).toBe(before);
+    expect(engine.parseSAN('dxe3')).toBeNull();
+  });
+
+  it('is honored when loading a FEN', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1')).toBe(true);
+    expect(engine.parseSAN('exd6')).not.toBeNull();
+  });
+});
+
+describe('exchanges', () => {
+  it('finds captures that win material', () => {
+    const engine = new ChessRules();
+    // The black queen on d4 is undefended; the pawn on b5 is defended
+    expect(engine.setPosition('4k3/8/p7/1p6/3q4/8/8/1Q1RK3 w - - 0 1')).toBe(
+      true
+    );
+    const sans = engine.getFreeCaptures().map(m => engine.moveToSAN(m));
+    expect(sans).toEqual(['Rxd4']);
+  });
+
+  it('scores exchanges by the cheapest recapture', () => {
+    const engine = new ChessRules();
+    // Pawn on e5 attacked by knight and rook, defended by the d6 pawn
+    expect(engine.setPosition('4k3/8/3p4/4p3/8/5N2/8/4RK2 w - - 0 1')).toBe(
+      true
+    );
+    const nxe5 = engine.parseSAN('Nxe5')!;
+    // N takes P (+100), P takes N (-320), R takes P (+100)
+    expect(engine.staticExchange(nxe5)).toBe(100 - 320 + 100);
+    expect(engine.getFreeCaptures()).toEqual([]);
+    expect(engine.staticExchange(engine.parseSAN('Kg2')!)).toBe(0);
+  });
+
+  it('lists both sides of a contested pawn cheapest first', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('4k3/8/3p4/4p3/8/5N2/8/4RK2 w - - 0 1')).toBe(
+      true
+    );
+    expect(engine.getExchangeParticipants(pos('e5'))).toEqual({
+      white: [
+        {
+          position: pos('f3'),
+          piece: { type: PieceType.Knight, color: Color.White },
+        },
+        {
+          position: pos('e1'),
+          piece: { type: PieceType.Rook, color: Color.White },
+        },
+      ],
+      black: [
+        {
+          position: pos('d6'),
+          piece: { type: PieceType.Pawn, color: Color.Black },
+        },
+      ],
+    });
+  });
+});
+
+describe('mates in one', () => {
+  it('finds the back-rank mate', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1')).toBe(true);
+    const mates = engine.getMatesInOne();
+    expect(mates.map(move => engine.moveToSAN(move))).toEqual(['Ra8#']);
+  });
+
+  it('is empty without a mate in one', () => {
+    const engine = new ChessRules();
+    expect(engine.getMatesInOne()).toEqual([]);
+  });
+
+  it('warns when the opponent threatens mate', () => {
+    const engine = new ChessRules();
+    // Black to move; White threatens Ra8#
+    expect(engine.setPosition('6k1/5ppp/8/8/8/8/8/R5K1 b - - 0 1')).toBe(true);
+    expect(engine.opponentThreatensMate()).toBe(true);
+    // With luft on h6 there is no threat
+    expect(engine.setPosition('6k1/5pp1/7p/8/8/8/8/R5K1 b - - 0 1')).toBe(true);
+    expect(engine.opponentThreatensMate()).toBe(false);
+    expect(new ChessRules().opponentThreatensMate()).toBe(false);
+  });
+});
+
+describe('EPD parsing', () => {
+  it('loads the position and returns bm and id operations', () => {
+    const engine = new ChessRules();
+    const ops = engine.setPositionFromEPD(
+      '2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - ' +
+        'bm Qg6; id "WAC.001";'
+    );
+    expect(ops).not.toBeNull();
+    expect(ops!.bestMoves).toEqual(['Qg6']);
+    expect(ops!.id).toBe('WAC.001');
+    expect(fenOf(engine)).toBe(
+      '2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1'
+    );
+    expect(engine.parseSAN(ops!.bestMoves[0])).not.toBeNull();
+  });
+
+  it('keeps multiple best moves and unknown opcodes', () => {
+    const engine = new ChessRules();
+    const ops = engine.setPositionFromEPD(
+      '4k3/8/8/8/8/8/8/R3K2R w KQ - bm O-O Ra8+; c0 "two moves win";'
+    );
+    expect(ops!.bestMoves).toEqual(['O-O', 'Ra8+']);
+    expect(ops!.id).toBeNull();
+    expect(ops!.all.c0).toEqual(['two moves win']);
+  });
+
+  it('rejects malformed records without touching the position', () => {
+    const engine = new ChessRules();
+    const before = fenOf(engine);
+    expect(engine.setPositionFromEPD('8/8/8 w - -')).toBeNull();
+    expect(
+      engine.setPositionFromEPD('4k3/8/8/8/8/8/8/4K3 w - - bm Kd2')
+    ).toBeNull();
+    expect(
+      engine.setPositionFromEPD('4k3/8/8/8/8/8/8/4K3 w - - id "open;')
+    ).toBeNull();
+    expect(fenOf(engine)).toBe(before);
+  });
+});
+
+describe('material signature', () => {
+  it('lists each side strongest piece first', () => {
+    const engine = new ChessRules();
+    expect(engine.getMaterialSignature()).toBe(
+      'KQRRBBNNPPPPPPPPvKQRRBBNNPPPPPPPP'
+    );
+    expect(engine.setPosition('8/8/8/3k4/8/8/8/Q3K3 w - - 0 1')).toBe(true);
+    expect(engine.getMaterialSignature()).toBe('KQvK');
+    expect(engine.setPosition('3rk3/8/8/8/8/4P3/8/R3K3 w - - 0 1')).toBe(true);
+    expect(engine.getMaterialSignature()).toBe('KRPvKR');
+  });
+});
+
+describe('mating material', () => {
+  it('classifies one side at a time', () => {
+    const engine = new ChessRules();
+    expect(engine.hasMatingMaterial(Color.White)).toBe(true);
+
+    expect(engine.setPosition('4k3/8/8/8/8/8/8/R3K3 w - - 0 1')).toBe(true);
+    expect(engine.hasMatingMaterial(Color.White)).toBe(true);
+    expect(engine.hasMatingMaterial(Color.Black)).toBe(false);
+
+    expect(engine.setPosition('4k3/8/8/8/8/8/8/1N2K1n1 w - - 0 1')).toBe(true);
+    expect(engine.hasMatingMaterial(Color.White)).toBe(false);
+    expect(engine.hasMatingMaterial(Color.Black)).toBe(false);
+
+    expect(engine.setPosition('4k3/8/8/8/8/8/8/1NB1K3 w - - 0 1')).toBe(true);
+    expect(engine.hasMatingMaterial(Color.White)).toBe(true);
+  });
+});
+
+describe('wrong bishop', () => {
+  const isDraw = (fen: string) => {
+    const engine = new ChessRules();
+    expect(engine.setPosition(fen)).toBe(true);
+    return engine.isWrongBishopDraw();
+  };
+
+  it('spots the dark-squared bishop with an a-pawn', () => {
+    expect(isDraw('1k6/8/8/8/P7/8/8/2B1K3 w - - 0 1')).toBe(true);
+    // Same for Black: h1 is a light square, the bishop is dark-squared
+    expect(isDraw('4k3/2b5/8/8/7p/8/8/6K1 b - - 0 1')).toBe(true);
+  });
+
+  it('is not a draw with the right bishop or a far-away king', () => {
+    // A light-squared bishop controls a8
+    expect(isDraw('1k6/8/8/8/P7/8/8/3BK3 w - - 0 1')).toBe(false);
+    // The black king cannot get back to a8 in time
+    expect(isDraw('8/8/8/8/P7/8/8/2B1K2k w - - 0 1')).toBe(false);
+    // Not a rook pawn
+    expect(isDraw('1k6/8/8/8/1P6/8/8/2B1K3 w - - 0 1')).toBe(false);
+  });
+});
+
+describe('basic endgames', () => {
+  /** Let the engine play the strong side against the given defence. */
+  function playOut(fen: string, pickReply: (moves: Move[]) => Move) {
+    const engine = new ChessRules();
+    expect(engine.setPosition(fen)).toBe(true);
+    for (let moves = 0; moves < 40; moves++) {
+      const move = engine.findBasicEndgameMove();
+      expect(move).not.toBeNull();
+      engine.makeMove(
+        { file: move!.fromFile, rank: move!.fromRank },
+        { file: move!.toFile, rank: move!.toRank }
+      );
+      const state = engine.getGameState();
+      if (state.isGameOver) return { engine, moves: moves + 1 };
+      const reply = pickReply(state.validMoves);
+      engine.makeMove(
+        { file: reply.fromFile, rank: reply.fromRank },
+        { file: reply.toFile, rank: reply.toRank }
+      );
+    }
+    return { engine, moves: Infinity };
+  }
+
+  it('mates with king and queen', { timeout: 60_000 }, () => {
+    const { engine, moves } = playOut(
+      '8/8/8/3k4/8/8/8/Q3K3 w - - 0 1',
+      replies => replies[0]
+    );
+    expect(engine.getGameState().result?.reason).toBe('checkmate');
+    expect(moves).toBeLessThanOrEqual(10);
+  });
+
+  it('mates with king and rook', { timeout: 60_000 }, () => {
+    const { engine, moves } = playOut(
+      '8/8/8/3k4/8/8/8/R3K3 w - - 0 1',
+      replies => replies[replies.length - 1]
+    );
+    expect(engine.getGameState().result?.reason).toBe('checkmate');
+    expect(moves).toBeLessThanOrEqual(16);
+  });
+
+  it('plays the strong side for Black too', { timeout: 60_000 }, () => {
+    const { engine } = playOut(
+      '4k3/8/8/8/3K4/8/8/7r b - - 0 1',
+      replies => replies[0]
+    );
+    expect(engine.getGameState().result).toEqual({
+      winner: Color.Black,
+      reason: 'checkmate',
+    });
+  });
+
+  it('never stalemates against any defence', { timeout: 60_000 }, () => {
+    for (let seed = 1; seed <= 5; seed++) {
+      const engine = new ChessRules();
+      expect(engine.setPosition('8/8/3k4/8/8/8/8/Q3K3 w - - 0 1')).toBe(true);
+      for (let ply = 0; ply < 40 && !engine.getGameState().isGameOver; ply++) {
+        const strongToMove = ply % 2 === 0;
+        const move = strongToMove
+          ? engine.findBasicEndgameMove()!
+          : engine.randomLegalMove(seed * 100 + ply)!;
+        if (strongToMove) {
+          expect(engine.moveCausesStalemate(move)).toBe(false);
+        }
+        engine.makeMove(
+          { file: move.fromFile, rank: move.fromRank },
+          { file: move.toFile, rank: move.toRank }
+        );
+      }
+      expect(engine.getGameState().result?.reason).toBe('checkmate');
+    }
+  });
+
+  it('avoids stalemating a cornered king', { timeout: 60_000 }, () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('k7/8/2K5/8/8/8/8/1Q6 w - - 0 1')).toBe(true);
+    const move = engine.findBasicEndgameMove()!;
+    expect(engine.moveCausesStalemate(move)).toBe(false);
+    engine.makeMove(
+      { file: move.fromFile, rank: move.fromRank },
+      { file: move.toFile, rank: move.toRank }
+    );
+    expect(engine.getGameState().result?.reason).toBe('checkmate');
+  });
+
+  it('returns null for other material', () => {
+    const engine = new ChessRules();
+    expect(engine.findBasicEndgameMove()).toBeNull();
+    expect(engine.setPosition('8/8/8/3k4/8/8/8/B3K3 w - - 0 1')).toBe(true);
+    expect(engine.findBasicEndgameMove()).toBeNull();
+    // The lone king's side to move
+    expect(engine.setPosition('8/8/8/3k4/8/8/8/Q3K3 b - - 0 1')).toBe(true);
+    expect(engine.findBasicEndgameMove()).toBeNull();
+  });
+});
+
+describe('move callback', () => {
+  it('reports made and undone moves with the resulting FEN', () => {
+    const engine = new ChessRules();
+    const calls: Array<[Move, string, boolean]> = [];
+    engine.setOnMove((move, fen, undone) => calls.push([move, fen, undone]));
+
+    engine.makeMove(pos('e2'), pos('e4'));
+    expect(engine.makeNullMove()).toBe(true);
+    expect(engine.undoMove()).toBe(true);
+    expect(engine.undoMove()).toBe(true);
+    expect(engine.makeMove(pos('e2'), pos('e5')).success).toBe(false);
+
+    const e2e4 = { fromFile: 4, fromRank: 1, toFile: 4, toRank: 3 };
+    expect(calls).toHaveLength(2);
+    expect(calls[0][0]).toEqual(e2e4);
+    expect(calls[0][1]).toBe(
+      'rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1'
+    );
+    expect(calls[0][2]).toBe(false);
+    expect(calls[1][0]).toEqual(e2e4);
+    expect(calls[1][1]).toBe(fenOf(new ChessRules()));
+    expect(calls[1][2]).toBe(true);
+  });
+
+  it('keeps the move when the callback throws', () => {
+    const engine = new ChessRules();
+    engine.setOnMove(() => {
+      throw new Error('listener failed');
+    });
+    expect(engine.makeMove(pos('e2'), pos('e4')).success).toBe(true);
+    expect(engine.getHistory()).toHaveLength(1);
+  });
+
+  it('can be removed and is not inherited by clones', () => {
+    const engine = new ChessRules();
+    let count = 0;
+    engine.setOnMove(() => count++);
+    engine.clone().makeMove(pos('e2'), pos('e4'));
+    engine.setOnMove(null);
+    engine.makeMove(pos('e2'), pos('e4'));
+    expect(count).toBe(0);
+  });
+});
+
+describe('move classification', () => {
+  const none = {
+    isCapture: false,
+    isCastle: false,
+    isEnPassant: false,
+    isPromotion: false,
+    isDoublePush: false,
+  };
+
+  it('classifies a move without making it', () => {
+    const engine = new ChessRules();
+    const fen = 'r3k3/1P6/8/3pP3/8/8/4P3/R3K2R w KQq d6 0 1';
+    expect(engine.setPosition(fen)).toBe(true);
+    const classify = (san: string) =>
+      engine.classifyMove(engine.parseSAN(san)!);
+
+    expect(classify('exd6')).toEqual({
+      ...none,
+      isCapture: true,
+      isEnPassant: true,
+    });
+    expect(classify('O-O')).toEqual({ ...none, isCastle: true });
+    expect(classify('bxa8=Q')).toEqual({
+      ...none,
+      isCapture: true,
+      isPromotion: true,
+    });
+    expect(classify('e4')).toEqual({ ...none, isDoublePush: true });
+    expect(classify('Rb1')).toEqual(none);
+    expect(fenOf(engine)).toBe(fen);
+  });
+
+  it('tells direct, discovered and double checks apart', () => {
+    const engine = new ChessRules();
+    // Rook on e1 behind the bishop on e4, black king on e8
+    expect(engine.setPosition('4k3/8/8/8/4B3/8/8/K3R3 w - - 0 1')).toBe(true);
+    const checkType = (san: string) =>
+      engine.getCheckType(engine.parseSAN(san)!);
+    expect(checkType('Bc6')).toBe('double');
+    expect(checkType('Bd5')).toBe('discovered');
+    expect(checkType('Kb2')).toBe('none');
+
+    expect(engine.setPosition('4k3/8/8/8/8/8/8/4K2R w K - 0 1')).toBe(true);
+    expect(checkType('Rh8')).toBe('direct');
+    expect(engine.setPosition('5k2/8/8/8/8/8/8/4K2R w K - 0 1')).toBe(true);
+    expect(checkType('O-O')).toBe('direct');
+  });
+
+  it('sees checks from castling rooks and en passant discoveries', () => {
+    const engine = new ChessRules();
+    const givesCheck = (san: string) =>
+      engine.givesCheck(engine.parseSAN(san)!);
+    expect(engine.setPosition('5k2/8/8/8/8/8/8/4K2R w K - 0 1')).toBe(true);
+    expect(givesCheck('O-O')).toBe(true);
+    expect(givesCheck('Kd2')).toBe(false);
+
+    // exd6 e.p. clears d5 and e5, opening the fifth rank to the queen
+    expect(engine.setPosition('8/8/8/k2pP2Q/8/8/8/4K3 w - d6 0 1')).toBe(true);
+    expect(givesCheck('exd6')).toBe(true);
+    const backwards = { fromFile: 4, fromRank: 4, toFile: 4, toRank: 2 };
+    expect(engine.givesCheck(backwards)).toBeNull();
+  });
+
+  it('returns null for an illegal check type query', () => {
+    const engine = new ChessRules();
+    const move = { fromFile: 4, fromRank: 1, toFile: 4, toRank: 4 };
+    expect(engine.getCheckType(move)).toBeNull();
+  });
+
+  it('returns null when the from square is empty', () => {
+    const engine = new ChessRules();
+    const move = { fromFile: 4, fromRank: 3, toFile: 4, toRank: 4 };
+    expect(engine.classifyMove(move)).toBeNull();
+  });
+});
+
+describe('opening names', () => {
+  it('names the longest matching book line', () => {
+    const engine = new ChessRules();
+    expect(engine.getOpeningName()).toBeNull();
+    playSAN(engine, 'e4', 'e5', 'Nf3', 'Nc6', 'Bc4');
+    expect(engine.getOpeningName()).toBe('Italian Game');
+    playSAN(engine, 'Bc5');
+    expect(engine.getOpeningName()).toBe('Giuoco Piano');
+    // Leaving the book keeps the last line reached
+    playSAN(engine, 'a3');
+    expect(engine.getOpeningName()).toBe('Giuoco Piano');
+  });
+
+  it('returns null out of book or from a set-up position', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'a4');
+    expect(engine.getOpeningName()).toBeNull();
+
+    expect(
+      engine.setPosition(
+        'rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 2'
+      )
+    ).toBe(true);
+    playSAN(engine, 'e4', 'c5');
+    expect(engine.getOpeningName()).toBeNull();
+  });
+});
+
+describe('checking destinations', () => {
+  it('lists the squares from which a piece gives check', () => {
+    const engine = new ChessRules();
+    expect(engine.setPosition('6k1/8/8/8/8/8/7K/R7 w - - 0 1')).toBe(true);
+    const squares = engine.getCheckingDestinations(pos('a1'));
+    expect(squares).toHaveLength(2);
+    expect(squares).toContainEqual(pos('a8'));
+    expect(squares).toContainEqual(pos('g1'));
+  });
+
+  it('counts under-promotions and discovered checks', () => {
+    const engine = new ChessRules();
+    // Only a knight promotion on e8 checks the king on d6
+    expect(engine.setPosition('8/4P3/3k4/8/8/8/8/4K3 w - - 0 1')).toBe(true);
+    expect(engine.getCheckingDestinations(pos('e7'))).toEqual([pos('e8')]);
+
+    // The bishop uncovers the rook on every move
+    expect(engine.setPosition('4k3/8/8/8/8/8/4B3/4RK2 w - - 0 1')).toBe(true);
+    expect(engine.getCheckingDestinations(pos('e2'))).toHaveLength(8);
+  });
+
+  it('is empty for pinned pieces and the side not to move', () => {
+    const engine = new ChessRules();
+    // The knight may not leave the pin to check the king on g6
+    expect(engine.setPosition('4r3/8/6k1/8/8/8/4N3/4K3 w - - 0 1')).toBe(true);
+    expect(engine.getCheckingDestinations(pos('e2'))).toEqual([]);
+    expect(engine.getCheckingDestinations(pos('e8'))).toEqual([]);
+  });
+});
+
+describe('free play', () => {
+  it('lets either side move and passes the turn on', () => {
+    const engine = new ChessRules();
+    engine.setFreePlay(true);
+    engine.makeMove(pos('e2'), pos('e4'));
+    expect(engine.makeMove(pos('d2'), pos('d4')).success).toBe(true);
+    expect(fenOf(engine)).toBe(
+      'rnbqkbnr/pppppppp/8/8/3PP3/8/PPP2PPP/RNBQKBNR b KQkq - 0 1'
+    );
+
+    // Undo restores the turn as it was before the out-of-turn move
+    expect(engine.undoMove()).toBe(true);
+    expect(engine.getGameState().currentPlayer).toBe(Color.Black);
+  });
+
+  it('still enforces legal movement', () => {
+    const engine = new ChessRules();
+    engine.setFreePlay(true);
+    playSAN(engine, 'e4');
+    const before = fenOf(engine);
+    expect(engine.makeMove(pos('e4'), pos('e6')).success).toBe(false);
+    expect(fenOf(engine)).toBe(before);
+  });
+
+  it('never lets the side not to move capture the king', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'e4', 'e5', 'Qh5', 'Nc6', 'Bc4', 'Nf6', 'Qxf7#');
+    engine.setFreePlay(true);
+    const before = fenOf(engine);
+    expect(engine.makeMove(pos('f7'), pos('e8')).success).toBe(false);
+    expect(fenOf(engine)).toBe(before);
+    expect(engine.getGameResult()).toEqual({
+      winner: Color.White,
+      reason: 'checkmate',
+    });
+  });
+
+  it('keeps turn order when disabled', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'e4');
+    expect(engine.makeMove(pos('d2'), pos('d4')).success).toBe(false);
+    engine.setFreePlay(true);
+    engine.setFreePlay(false);
+    expect(engine.makeMove(pos('d2'), pos('d4')).success).toBe(false);
+  });
+});
+
+describe('square change tracking', () => {
+  it('records the ply of the last change to each square', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'e4', 'd5', 'exd5');
+    expect(engine.getSquareLastChanged(pos('e2'))).toBe(1);
+    expect(engine.getSquareLastChanged(pos('e4'))).toBe(3);
+    expect(engine.getSquareLastChanged(pos('d5'))).toBe(3);
+    expect(engine.getSquareLastChanged(pos('d7'))).toBe(2);
+    expect(engine.getSquareLastChanged(pos('a1'))).toBe(0);
+
+    engine.undoMove();
+    expect(engine.getSquareLastChanged(pos('e4'))).toBe(1);
+    expect(engine.getSquareLastChanged(pos('d5'))).toBe(2);
+  });
+
+  it('updates every square touched by castling and en passant', () => {
+    const engine = new ChessRules();
+    const fen = 'r3k3/8/8/3pP3/8/8/8/4K2R w Kq d6 0 1';
+    expect(engine.setPosition(fen)).toBe(true);
+    playSAN(engine, 'exd6', 'O-O-O');
+    for (const square of ['e5', 'd5', 'd6']) {
+      expect(engine.getSquareLastChanged(pos(square))).toBe(1);
+    }
+    for (const square of ['a8', 'c8', 'd8', 'e8']) {
+      expect(engine.getSquareLastChanged(pos(square))).toBe(2);
+    }
+    expect(engine.getSquareLastChanged(pos('b8'))).toBe(0);
+  });
+});
+
+describe('history navigation', () => {
+  it('jumps back and forth while keeping the whole game', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'e4', 'e5', 'Nf3', 'Nc6', 'Bb5');
+    const end = fenOf(engine);
+    expect(engine.setMoveComment(4, 'The Spanish')).toBe(true);
+
+    expect(engine.gotoPly(2)).toBe(true);
+    expect(engine.getCurrentPly()).toBe(2);
+    const afterTwo = new ChessRules();
+    playSAN(afterTwo, 'e4', 'e5');
+    expect(fenOf(engine)).toBe(fenOf(afterTwo));
+
+    expect(engine.gotoPly(0)).toBe(true);
+    expect(fenOf(engine)).toBe(fenOf(new ChessRules()));
+    expect(engine.gotoPly(5)).toBe(true);
+    expect(fenOf(engine)).toBe(end);
+    expect(engine.getHistory()[4].comment).toBe('The Spanish');
+  });
+
+  it('refuses a ply whose moves no longer replay', () => {
+    const engine = new ChessRules();
+    engine.setFreePlay(true);
+    playSAN(engine, 'e4');
+    expect(engine.makeMove(pos('d2'), pos('d4')).success).toBe(true);
+    engine.setFreePlay(false);
+
+    expect(engine.gotoPly(1)).toBe(true);
+    const before = fenOf(engine);
+    expect(engine.gotoPly(2)).toBe(false);
+    expect(fenOf(engine)).toBe(before);
+    expect(engine.getCurrentPly()).toBe(1);
+  });
+
+  it('refuses to replay from a start that cannot be loaded', () => {
+    const engine = ChessRules.empty();
+    expect(engine.gotoPly(0)).toBe(false);
+    expect(fenOf(engine)).toBe('8/8/8/8/8/8/8/8 w - - 0 1');
+  });
+
+  it('rejects plies outside the game', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'd4');
+    expect(engine.gotoPly(2)).toBe(false);
+    expect(engine.gotoPly(-1)).toBe(false);
+    expect(engine.getCurrentPly()).toBe(1);
+  });
+
+  it('keeps the moves ahead until a different move is played', () => {
+    const engine = new ChessRules();
+    playSAN(engine, 'e4', 'e5', 'Nf3');
+    expect(engine.gotoPly(1)).toBe(true);
+    playSAN(engine, 'e5');
+    expect(engine.gotoPly(3)).toBe(true);
+
+    expect(engine.gotoPly(1)).toBe(true);
+    playSAN(engine, 'c5');
+    expect(engine.gotoPly(3)).toBe(false);
+    expect(engine.getCurrentPly()).toBe(2);
+  });
 });